@@ -0,0 +1,132 @@
+pub type Result<T> = std::result::Result<T, YamlError>;
+
+// YamlError is the set of failures returned by store operations. Natives
+// return these as codes, mirrored by the YAML_ERROR enumerator in yaml.inc.
+// Success is always 0.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum YamlError {
+    // The node ID does not exist or has already been deleted.
+    InvalidNode,
+    // The node or the value it holds is not of the type the native expects.
+    WrongType,
+    // The object does not contain the requested key.
+    MissingKey,
+    // An index is out of range, an output array is too small or an integer is
+    // too large for a cell.
+    OutOfRange,
+    // A path expression is malformed.
+    InvalidPath,
+    // The input could not be decoded.
+    Parse,
+    // The node could not be encoded.
+    Encode,
+    // A file could not be read or written or is outside of scriptfiles.
+    File,
+    // A schema passed to YAML_Validate is malformed.
+    Schema,
+    // A document does not match the schema it was validated against.
+    Validation,
+    // A format spec passed to YAML_Pack or YAML_Unpack is malformed.
+    Format,
+    // There are too many live nodes to allocate another.
+    PoolFull,
+    // The segment at this zero-based index of a path expression could not be
+    // resolved, so scripts can tell which part of a path failed.
+    PathSegment(usize),
+}
+
+impl YamlError {
+    pub fn code(self) -> i32 {
+        match self {
+            YamlError::InvalidNode => 1,
+            YamlError::WrongType => 2,
+            YamlError::MissingKey => 3,
+            YamlError::OutOfRange => 4,
+            YamlError::InvalidPath => 5,
+            YamlError::Parse => 6,
+            YamlError::Encode => 7,
+            YamlError::File => 8,
+            YamlError::Schema => 9,
+            YamlError::Validation => 10,
+            YamlError::Format => 11,
+            YamlError::PoolFull => 12,
+            YamlError::PathSegment(i) => 100 + i as i32,
+        }
+    }
+}
+
+// ParseError describes a decoding failure in enough detail for a script to
+// report where its input is broken. Line and column are 1-based and -1 when the
+// error has no location.
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    pub code: YamlError,
+    pub message: String,
+    pub line: i32,
+    pub column: i32,
+}
+
+impl ParseError {
+    pub fn new(code: YamlError, message: String) -> Self {
+        ParseError {
+            code,
+            message,
+            line: -1,
+            column: -1,
+        }
+    }
+}
+
+// Input that decoded fine can still fail to be allocated as a node, which has
+// no location.
+impl From<YamlError> for ParseError {
+    fn from(code: YamlError) -> Self {
+        let message = match code {
+            YamlError::PoolFull => "too many live nodes".to_string(),
+            code => format!("{:?}", code),
+        };
+        ParseError::new(code, message)
+    }
+}
+
+impl From<serde_yaml::Error> for ParseError {
+    fn from(e: serde_yaml::Error) -> Self {
+        match e.location() {
+            Some(l) => ParseError {
+                code: YamlError::Parse,
+                message: e.to_string(),
+                line: l.line() as i32,
+                column: l.column() as i32,
+            },
+            None => ParseError::new(YamlError::Parse, e.to_string()),
+        }
+    }
+}
+
+impl From<serde_json::Error> for ParseError {
+    fn from(e: serde_json::Error) -> Self {
+        match e.line() {
+            0 => ParseError::new(YamlError::Parse, e.to_string()),
+            line => ParseError {
+                code: YamlError::Parse,
+                message: e.to_string(),
+                line: line as i32,
+                column: e.column() as i32,
+            },
+        }
+    }
+}
+
+impl From<toml::de::Error> for ParseError {
+    fn from(e: toml::de::Error) -> Self {
+        match e.line_col() {
+            Some((line, column)) => ParseError {
+                code: YamlError::Parse,
+                message: e.to_string(),
+                line: line as i32 + 1,
+                column: column as i32 + 1,
+            },
+            None => ParseError::new(YamlError::Parse, e.to_string()),
+        }
+    }
+}
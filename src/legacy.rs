@@ -0,0 +1,203 @@
+use serde_yaml::{Mapping, Value};
+
+use crate::error::{ParseError, YamlError};
+
+// Conversions between nodes and the INI and TOML formats older scripts and
+// tools store their data in, so it can be migrated to YAML in-game.
+
+// -
+// INI
+// -
+
+// from_ini decodes dini and y_ini style files. Keys before the first section
+// header are set on the root and each `[section]` becomes a nested object.
+// Lines starting with `;` or `#` are comments. Since INI has no types, values
+// that look like integers, floats or booleans are decoded as such and anything
+// else is a string.
+pub fn from_ini(input: &str) -> Result<Value, ParseError> {
+    let mut root = Mapping::new();
+    let mut section: Option<Value> = None;
+    for (i, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            let name = Value::String(line[1..line.len() - 1].trim().to_string());
+            if !matches!(root.get(&name), Some(Value::Mapping(_))) {
+                root.insert(name.clone(), Value::Mapping(Mapping::new()));
+            }
+            section = Some(name);
+            continue;
+        }
+        let (key, value) = match line.find('=') {
+            Some(n) => (line[..n].trim(), line[n + 1..].trim()),
+            None => {
+                return Err(ParseError {
+                    code: YamlError::Parse,
+                    message: format!("expected `key = value` or `[section]`: {}", line),
+                    line: i as i32 + 1,
+                    column: 1,
+                })
+            }
+        };
+        let target = match &section {
+            Some(name) => match root.get_mut(name) {
+                Some(Value::Mapping(m)) => m,
+                _ => unreachable!("sections are always inserted as mappings"),
+            },
+            None => &mut root,
+        };
+        target.insert(Value::String(key.to_string()), ini_scalar(value));
+    }
+    Ok(Value::Mapping(root))
+}
+
+fn ini_scalar(s: &str) -> Value {
+    if s.len() >= 2 && s.starts_with('"') && s.ends_with('"') {
+        return Value::String(s[1..s.len() - 1].to_string());
+    }
+    if let Ok(v) = s.parse::<i64>() {
+        return v.into();
+    }
+    // Rust also parses words like `inf` and `NaN`, which are more likely to be
+    // strings in an INI file.
+    let numeric = s.chars().all(|c| c.is_ascii_digit() || "+-.eE".contains(c));
+    if let (true, Ok(v)) = (numeric, s.parse::<f64>()) {
+        return v.into();
+    }
+    match s {
+        "true" => true.into(),
+        "false" => false.into(),
+        _ => Value::String(s.to_string()),
+    }
+}
+
+// to_ini encodes an object node as INI. Scalars on the root are written before
+// any section and objects on the root are written as sections, which may only
+// contain scalars.
+pub fn to_ini(v: &Value) -> Result<String, String> {
+    let root = v.as_mapping().ok_or("INI can only encode an object")?;
+    let mut out = String::new();
+    for (k, v) in root {
+        if !v.is_mapping() {
+            out.push_str(&format!("{} = {}\n", ini_key(k)?, ini_value(v)?));
+        }
+    }
+    for (k, v) in root {
+        if let Value::Mapping(section) = v {
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            out.push_str(&format!("[{}]\n", ini_section(k)?));
+            for (k, v) in section {
+                out.push_str(&format!("{} = {}\n", ini_key(k)?, ini_value(v)?));
+            }
+        }
+    }
+    Ok(out)
+}
+
+// ini_key fails for keys that would be read back differently, such as those
+// containing `=` or starting like a comment or section header.
+fn ini_key(k: &Value) -> Result<String, String> {
+    let s = match k {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        _ => return Err("INI keys must be strings".to_string()),
+    };
+    if s.contains('=') || s.starts_with(&[';', '#', '['][..]) || !ini_plain(&s) {
+        return Err(format!("INI cannot encode the key {:?}", s));
+    }
+    Ok(s)
+}
+
+fn ini_section(k: &Value) -> Result<String, String> {
+    let s = ini_key(k)?;
+    if s.contains(']') {
+        return Err(format!("INI cannot encode the section {:?}", s));
+    }
+    Ok(s)
+}
+
+// ini_plain reports whether `s` fits on one line without being trimmed.
+fn ini_plain(s: &str) -> bool {
+    !s.contains(&['\n', '\r'][..]) && s.trim() == s
+}
+
+fn ini_value(v: &Value) -> Result<String, String> {
+    match v {
+        Value::Null => Ok(String::new()),
+        Value::Bool(b) => Ok(b.to_string()),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::String(s) if s.contains('\n') => {
+            Err("INI values cannot span multiple lines".to_string())
+        }
+        // Strings that would be read back as another type or lose surrounding
+        // whitespace are quoted.
+        Value::String(s) if s.trim() != s || ini_scalar(s) != Value::String(s.clone()) => {
+            Ok(format!("\"{}\"", s))
+        }
+        Value::String(s) => Ok(s.clone()),
+        _ => Err("INI sections can only contain scalars".to_string()),
+    }
+}
+
+// -
+// TOML
+// -
+
+// from_toml decodes TOML. Dates and times, which YAML nodes have no type for,
+// are decoded as strings.
+pub fn from_toml(input: &str) -> Result<Value, ParseError> {
+    let v: toml::Value = input.parse()?;
+    Ok(from_toml_value(v))
+}
+
+fn from_toml_value(v: toml::Value) -> Value {
+    match v {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => i.into(),
+        toml::Value::Float(f) => f.into(),
+        toml::Value::Boolean(b) => b.into(),
+        toml::Value::Datetime(d) => Value::String(d.to_string()),
+        toml::Value::Array(a) => Value::Sequence(a.into_iter().map(from_toml_value).collect()),
+        toml::Value::Table(t) => Value::Mapping(
+            t.into_iter()
+                .map(|(k, v)| (Value::String(k), from_toml_value(v)))
+                .collect(),
+        ),
+    }
+}
+
+// to_toml encodes an object node as TOML, which has no null and only allows
+// string keys.
+pub fn to_toml(v: &Value) -> Result<String, String> {
+    match to_toml_value(v)? {
+        v @ toml::Value::Table(_) => toml::to_string(&v).map_err(|e| e.to_string()),
+        _ => Err("TOML can only encode an object".to_string()),
+    }
+}
+
+fn to_toml_value(v: &Value) -> Result<toml::Value, String> {
+    Ok(match v {
+        Value::Null => return Err("TOML cannot encode null".to_string()),
+        Value::Bool(b) => toml::Value::Boolean(*b),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => toml::Value::Integer(i),
+            None => toml::Value::Float(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => toml::Value::String(s.clone()),
+        Value::Sequence(s) => {
+            toml::Value::Array(s.iter().map(to_toml_value).collect::<Result<_, _>>()?)
+        }
+        Value::Mapping(m) => {
+            let mut t = toml::value::Table::new();
+            for (k, v) in m {
+                let k = k.as_str().ok_or("TOML keys must be strings")?;
+                t.insert(k.to_string(), to_toml_value(v)?);
+            }
+            toml::Value::Table(t)
+        }
+    })
+}
@@ -1,18 +1,41 @@
 #[macro_use]
 extern crate enum_primitive;
 
+mod error;
+mod legacy;
+mod merge;
+mod node;
+mod pack;
+mod path;
 mod plugin;
 mod pool;
+mod roundtrip;
+mod schema;
+mod store;
 
 use crate::plugin::Plugin;
-use crate::pool::GarbageCollectedPool;
+use crate::store::Store;
 use samp::initialize_plugin;
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
 initialize_plugin!(
     natives: [
             Plugin::yaml_parse,
             Plugin::yaml_stringify,
+            Plugin::yaml_parse_all,
+            Plugin::yaml_stringify_all,
+            Plugin::yaml_parse_json,
+            Plugin::yaml_stringify_json,
+            Plugin::yaml_parse_ini,
+            Plugin::yaml_stringify_ini,
+            Plugin::yaml_parse_toml,
+            Plugin::yaml_stringify_toml,
+            Plugin::yaml_parse_file,
+            Plugin::yaml_save_file,
+            Plugin::yaml_parse_round_trip,
+            Plugin::yaml_parse_file_round_trip,
+            Plugin::yaml_get_last_error,
             Plugin::yaml_node_type,
             Plugin::yaml_object,
             Plugin::yaml_int,
@@ -23,22 +46,73 @@ initialize_plugin!(
             Plugin::yaml_append,
             Plugin::yaml_set_object,
             Plugin::yaml_set_int,
+            Plugin::yaml_set_int64,
+            Plugin::yaml_set_int_string,
             Plugin::yaml_set_float,
             Plugin::yaml_set_bool,
             Plugin::yaml_set_string,
+            Plugin::yaml_set_path_node,
+            Plugin::yaml_set_path_int,
+            Plugin::yaml_set_path_float,
+            Plugin::yaml_set_path_bool,
+            Plugin::yaml_set_path_string,
+            Plugin::yaml_has_key,
+            Plugin::yaml_remove_key,
+            Plugin::yaml_key_type,
             Plugin::yaml_get_object,
+            Plugin::yaml_get_object_ref,
             Plugin::yaml_get_int,
             Plugin::yaml_get_float,
             Plugin::yaml_get_bool,
             Plugin::yaml_get_string,
+            Plugin::yaml_get_int64,
+            Plugin::yaml_get_int_string,
+            Plugin::yaml_get_int_loose,
+            Plugin::yaml_get_float_loose,
+            Plugin::yaml_get_bool_loose,
+            Plugin::yaml_get_string_loose,
+            Plugin::yaml_get_int_or,
+            Plugin::yaml_get_float_or,
+            Plugin::yaml_get_bool_or,
+            Plugin::yaml_get_string_or,
             Plugin::yaml_get_array,
             Plugin::yaml_array_length,
             Plugin::yaml_array_object,
+            Plugin::yaml_array_object_ref,
+            Plugin::yaml_array_int,
+            Plugin::yaml_array_float,
+            Plugin::yaml_array_bool,
+            Plugin::yaml_array_string,
+            Plugin::yaml_array_to_ints,
+            Plugin::yaml_array_to_floats,
+            Plugin::yaml_array_push,
+            Plugin::yaml_array_insert,
+            Plugin::yaml_array_remove,
+            Plugin::yaml_array_set,
+            Plugin::yaml_array_push_int,
+            Plugin::yaml_array_push_float,
+            Plugin::yaml_array_push_bool,
+            Plugin::yaml_array_push_string,
+            Plugin::yaml_object_length,
+            Plugin::yaml_object_key,
+            Plugin::yaml_object_value,
+            Plugin::yaml_get_path_int,
+            Plugin::yaml_get_path_float,
+            Plugin::yaml_get_path_bool,
+            Plugin::yaml_get_path_string,
+            Plugin::yaml_get_path_node,
             Plugin::yaml_get_node_int,
             Plugin::yaml_get_node_float,
             Plugin::yaml_get_node_bool,
             Plugin::yaml_get_node_string,
+            Plugin::yaml_pack,
+            Plugin::yaml_unpack,
+            Plugin::yaml_validate,
             Plugin::yaml_toggle_gc,
+            Plugin::yaml_toggle_debug,
+            Plugin::yaml_toggle_loose_types,
+            Plugin::yaml_pool_stats,
+            Plugin::yaml_dump_live_nodes,
             Plugin::yaml_cleanup
     ],
     {
@@ -55,7 +129,10 @@ initialize_plugin!(
             .apply();
 
         Plugin {
-            yaml_nodes: Arc::new(Mutex::new(GarbageCollectedPool::default())),
+            yaml_nodes: Arc::new(Mutex::new(Store::new("scriptfiles"))),
+            parse_errors: HashMap::new(),
+            owners: HashMap::new(),
+            next_owner: 0,
         }
     }
 );
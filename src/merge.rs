@@ -0,0 +1,76 @@
+use serde_yaml::{Mapping, Value};
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::Marker;
+
+// resolve applies YAML 1.1 merge keys throughout `v`: every `<<` key in a
+// mapping is removed and the entries of the mapping it points to, or of each
+// mapping in the sequence it points to, are added to the mapping unless it
+// already has them. Earlier mappings in a sequence take precedence over later
+// ones. Returns an error message if a `<<` key points to anything else.
+pub fn resolve(v: &mut Value) -> Result<(), String> {
+    match v {
+        Value::Mapping(m) => {
+            for (_, v) in m.iter_mut() {
+                resolve(v)?;
+            }
+            let key = Value::String("<<".to_string());
+            match m.remove(&key) {
+                Some(Value::Mapping(from)) => merge(m, from),
+                Some(Value::Sequence(from)) => {
+                    for v in from {
+                        match v {
+                            Value::Mapping(from) => merge(m, from),
+                            _ => {
+                                return Err(
+                                    "merge key sequence must only contain mappings".to_string()
+                                )
+                            }
+                        }
+                    }
+                }
+                Some(_) => {
+                    return Err("merge key must be a mapping or a sequence of mappings".to_string())
+                }
+                None => (),
+            }
+        }
+        Value::Sequence(s) => {
+            for v in s.iter_mut() {
+                resolve(v)?;
+            }
+        }
+        _ => (),
+    }
+    Ok(())
+}
+
+fn merge(m: &mut Mapping, from: Mapping) {
+    for (k, v) in from {
+        if !m.contains_key(&k) {
+            m.insert(k, v);
+        }
+    }
+}
+
+// has_aliases reports whether `input` refers to any anchors, meaning that
+// parts of the decoded value are copies of others.
+pub fn has_aliases(input: &str) -> bool {
+    // Every alias starts with an asterisk, which is rare enough in documents
+    // without any to skip scanning them.
+    if !input.contains('*') {
+        return false;
+    }
+    let mut aliases = Aliases(false);
+    let _ = Parser::new(input.chars()).load(&mut aliases, false);
+    aliases.0
+}
+
+struct Aliases(bool);
+
+impl MarkedEventReceiver for Aliases {
+    fn on_event(&mut self, ev: Event, _: Marker) {
+        if let Event::Alias(_) = ev {
+            self.0 = true;
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::path::{self, Segment};
+use crate::roundtrip::Document;
+
+// Node is what a handle refers to: a document, or a part of one addressed by a
+// path from its root. Handles to children share their parent's document
+// instead of copying it, so taking a handle to part of a large document costs
+// no more than the length of the path. A child handle copies its own subtree
+// when written to, and before a document is written to through its root the
+// store detaches every child sharing it, which copies only their subtrees, so
+// writes through one handle are never seen through another.
+#[derive(Clone)]
+pub struct Node {
+    root: Arc<serde_yaml::Value>,
+    path: Vec<Segment>,
+    // Nodes parsed in round-trip mode keep the text they were parsed from so
+    // they can be rendered back with their formatting intact.
+    document: Option<Arc<Document>>,
+    // How many times each array in the node, keyed by its path, has had items
+    // inserted or removed, which moves the items after them.
+    resized: HashMap<Vec<Segment>, u64>,
+}
+
+impl Node {
+    pub fn new(v: serde_yaml::Value) -> Self {
+        Node {
+            root: Arc::new(v),
+            path: Vec::new(),
+            document: None,
+            resized: HashMap::new(),
+        }
+    }
+
+    pub fn with_document(v: serde_yaml::Value, document: Document) -> Self {
+        Node {
+            root: Arc::new(v),
+            path: Vec::new(),
+            document: Some(Arc::new(document)),
+            resized: HashMap::new(),
+        }
+    }
+
+    pub fn document(&self) -> Option<&Document> {
+        self.document.as_deref()
+    }
+
+    // child returns a node sharing this node's document, addressing the value
+    // at `segments` below this one. Returns None if there is no such value.
+    pub fn child(&self, segments: &[Segment]) -> Option<Node> {
+        path::lookup(self.get(), segments).ok()?;
+        let mut path = self.path.clone();
+        path.extend_from_slice(segments);
+        Some(Node {
+            root: self.root.clone(),
+            path,
+            document: None,
+            resized: HashMap::new(),
+        })
+    }
+
+    // resize records that the array at `path` had items inserted or removed.
+    pub fn resize(&mut self, path: &[Segment]) {
+        *self.resized.entry(path.to_vec()).or_insert(0) += 1;
+    }
+
+    // layout returns a number that changes whenever an array along `path` is
+    // resized, so a path to an array item can be checked for still pointing at
+    // the same item.
+    pub fn layout(&self, path: &[Segment]) -> u64 {
+        path.iter()
+            .enumerate()
+            .filter(|(_, s)| matches!(s, Segment::Index(_)))
+            .map(|(i, _)| self.resized.get(&path[..i]).copied().unwrap_or(0))
+            .sum()
+    }
+
+    // shared reports whether this node is the root of a document that other
+    // nodes still hold.
+    pub fn shared(&self) -> bool {
+        self.path.is_empty() && Arc::strong_count(&self.root) > 1
+    }
+
+    // document_id identifies the document behind the node, which is the same
+    // for a node and the children taken from it until either is detached.
+    pub fn document_id(&self) -> usize {
+        Arc::as_ptr(&self.root) as usize
+    }
+
+    // detach gives a child node its own copy of its subtree so that it no
+    // longer holds on to the rest of the document.
+    pub fn detach(&mut self) {
+        if !self.path.is_empty() {
+            self.root = Arc::new(self.get().clone());
+            self.path.clear();
+        }
+    }
+
+    pub fn get(&self) -> &serde_yaml::Value {
+        // The document behind a node is never modified while it is shared, so
+        // the path always resolves.
+        path::lookup(&self.root, &self.path).expect("node path is always valid")
+    }
+
+    pub fn get_mut(&mut self) -> &mut serde_yaml::Value {
+        self.detach();
+        Arc::make_mut(&mut self.root)
+    }
+
+    pub fn into_value(self) -> serde_yaml::Value {
+        if !self.path.is_empty() {
+            return self.get().clone();
+        }
+        match Arc::try_unwrap(self.root) {
+            Ok(v) => v,
+            Err(root) => (*root).clone(),
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use encoding_rs::WINDOWS_1251;
+use serde_yaml::Value;
+
+use crate::path::{self, Segment};
+
+// Field is one entry of a format spec as used by YAML_Pack and YAML_Unpack,
+// such as `name:s[24]`, mapping the value at a path to cells of a Pawn array.
+pub struct Field {
+    pub path: Vec<Segment>,
+    pub cell: Cell,
+}
+
+pub enum Cell {
+    Int,
+    Float,
+    Bool,
+    // A string taking up this many cells, including the terminator.
+    String(usize),
+}
+
+impl Cell {
+    pub fn size(&self) -> usize {
+        match self {
+            Cell::String(n) => *n,
+            _ => 1,
+        }
+    }
+}
+
+// parse_format splits a format spec into fields. Fields are separated by
+// whitespace or commas and written as `path:type`, where the type is `i` for
+// an integer, `f` for a float, `b` for a boolean or `s[N]` for a string of N
+// cells, for example `name:s[24] score:i stats.ratio:f`. Returns None if the
+// spec is malformed.
+pub fn parse_format(format: &str) -> Option<Vec<Field>> {
+    let mut fields = Vec::new();
+    for field in format.split(|c: char| c.is_whitespace() || c == ',') {
+        if field.is_empty() {
+            continue;
+        }
+        let n = field.rfind(':')?;
+        let path = path::parse(&field[..n])?;
+        let cell = match &field[n + 1..] {
+            "i" => Cell::Int,
+            "f" => Cell::Float,
+            "b" => Cell::Bool,
+            s if s.starts_with("s[") && s.ends_with(']') => match s[2..s.len() - 1].parse() {
+                Ok(0) | Err(_) => return None,
+                Ok(n) => Cell::String(n),
+            },
+            _ => return None,
+        };
+        fields.push(Field { path, cell });
+    }
+    Some(fields)
+}
+
+// write_string copies a string into cells the way strings are passed to
+// natives, truncating it to leave room for the terminator.
+pub fn write_string(cells: &mut [i32], s: &str) {
+    let (bytes, _, _) = WINDOWS_1251.encode(s);
+    let n = bytes.len().min(cells.len() - 1);
+    for (cell, b) in cells.iter_mut().zip(&bytes[..n]) {
+        *cell = *b as i32;
+    }
+    cells[n] = 0;
+}
+
+// read_string reads a string from cells up to its terminator or the end of the
+// cells.
+pub fn read_string(cells: &[i32]) -> Value {
+    let bytes: Vec<u8> = cells
+        .iter()
+        .take_while(|c| **c != 0)
+        .map(|c| *c as u8)
+        .collect();
+    let (s, _) = WINDOWS_1251.decode_without_bom_handling(&bytes);
+    Value::String(s.into_owned())
+}
@@ -0,0 +1,180 @@
+use std::fmt;
+
+// Segment is a single step of a path expression such as `server.limits[0]`,
+// either a key into a mapping or an index into a sequence.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Segment::Key(k) => write!(f, "{}", k),
+            Segment::Index(i) => write!(f, "[{}]", i),
+        }
+    }
+}
+
+// parse splits a path expression into segments. Keys are separated by `.` and
+// sequence indices are written in brackets, for example `a.b[2].c` or `[0].a`.
+// Returns None if the expression is malformed.
+pub fn parse(path: &str) -> Option<Vec<Segment>> {
+    let mut segments = Vec::new();
+    let mut chars = path.chars().peekable();
+    let mut key = String::new();
+    let mut expect_key = true;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => {
+                if key.is_empty() && expect_key {
+                    return None;
+                }
+                if !key.is_empty() {
+                    segments.push(Segment::Key(std::mem::take(&mut key)));
+                }
+                expect_key = true;
+            }
+            '[' => {
+                if !key.is_empty() {
+                    segments.push(Segment::Key(std::mem::take(&mut key)));
+                } else if expect_key && !segments.is_empty() {
+                    return None;
+                }
+                let mut index = String::new();
+                loop {
+                    match chars.next() {
+                        Some(']') => break,
+                        Some(d) if d.is_ascii_digit() => index.push(d),
+                        _ => return None,
+                    }
+                }
+                segments.push(Segment::Index(index.parse().ok()?));
+                expect_key = false;
+                match chars.peek() {
+                    None | Some('.') | Some('[') => (),
+                    _ => return None,
+                }
+            }
+            ']' => return None,
+            c => key.push(c),
+        }
+    }
+
+    if !key.is_empty() {
+        segments.push(Segment::Key(key));
+    } else if expect_key {
+        return None;
+    }
+
+    Some(segments)
+}
+
+// format writes segments back out as a path expression, the reverse of parse.
+pub fn format(segments: &[Segment]) -> String {
+    let mut out = String::new();
+    for s in segments {
+        if let Segment::Key(_) = s {
+            if !out.is_empty() {
+                out.push('.');
+            }
+        }
+        out.push_str(&s.to_string());
+    }
+    out
+}
+
+// lookup walks `segments` from `root` without copying any part of the tree. On
+// failure, returns the index of the segment that could not be resolved.
+pub fn lookup<'a>(
+    root: &'a serde_yaml::Value,
+    segments: &[Segment],
+) -> Result<&'a serde_yaml::Value, usize> {
+    let mut v = root;
+    for (i, segment) in segments.iter().enumerate() {
+        let next = match segment {
+            Segment::Key(k) => v
+                .as_mapping()
+                .and_then(|m| m.get(&serde_yaml::Value::String(k.clone()))),
+            Segment::Index(n) => v.as_sequence().and_then(|s| s.get(*n)),
+        };
+        v = match next {
+            Some(v) => v,
+            None => return Err(i),
+        };
+    }
+    Ok(v)
+}
+
+// lookup_mut is lookup for a mutable value.
+pub fn lookup_mut<'a>(
+    root: &'a mut serde_yaml::Value,
+    segments: &[Segment],
+) -> Result<&'a mut serde_yaml::Value, usize> {
+    let mut v = root;
+    for (i, segment) in segments.iter().enumerate() {
+        let next = match segment {
+            Segment::Key(k) => v
+                .as_mapping_mut()
+                .and_then(|m| m.get_mut(&serde_yaml::Value::String(k.clone()))),
+            Segment::Index(n) => v.as_sequence_mut().and_then(|s| s.get_mut(*n)),
+        };
+        v = match next {
+            Some(v) => v,
+            None => return Err(i),
+        };
+    }
+    Ok(v)
+}
+
+// lookup_or_create walks `segments` from `root`, creating any missing mappings
+// and sequences along the way. A missing key is inserted and an index equal to
+// the length of a sequence appends to it, with null standing in for the value.
+// On failure, returns the index of the segment that conflicts with an existing
+// value of another type.
+pub fn lookup_or_create<'a>(
+    root: &'a mut serde_yaml::Value,
+    segments: &[Segment],
+) -> Result<&'a mut serde_yaml::Value, usize> {
+    let mut v = root;
+    for (i, segment) in segments.iter().enumerate() {
+        match segment {
+            Segment::Key(_) if v.is_null() => {
+                *v = serde_yaml::Value::Mapping(serde_yaml::Mapping::new());
+            }
+            Segment::Index(_) if v.is_null() => {
+                *v = serde_yaml::Value::Sequence(Vec::new());
+            }
+            _ => (),
+        }
+        v = match segment {
+            Segment::Key(k) => {
+                let m = match v.as_mapping_mut() {
+                    Some(m) => m,
+                    None => return Err(i),
+                };
+                let k = serde_yaml::Value::String(k.clone());
+                if !m.contains_key(&k) {
+                    m.insert(k.clone(), serde_yaml::Value::Null);
+                }
+                m.get_mut(&k).unwrap()
+            }
+            Segment::Index(n) => {
+                let s = match v.as_sequence_mut() {
+                    Some(s) => s,
+                    None => return Err(i),
+                };
+                if *n == s.len() {
+                    s.push(serde_yaml::Value::Null);
+                }
+                match s.get_mut(*n) {
+                    Some(v) => v,
+                    None => return Err(i),
+                }
+            }
+        };
+    }
+    Ok(v)
+}
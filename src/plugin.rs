@@ -1,43 +1,89 @@
-use log::{debug, error};
+use log::{error, info};
+use samp::amx::AmxIdent;
 use samp::native;
 use samp::prelude::*;
 use samp::SampPlugin;
-use serde_yaml;
-use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
 
-use crate::pool::GarbageCollectedPool;
+use crate::error::{ParseError, YamlError};
+use crate::store::Store;
 
+// Node-returning natives return this instead of a node ID on failure, mirrored
+// by INVALID_YAML_NODE in yaml.inc.
+const INVALID_NODE: i32 = -1;
+
+// Plugin adapts the natives to the Store, converting between AMX arguments and
+// Rust values. Store errors are returned to scripts as YAML_ERROR codes.
 pub struct Plugin {
-    pub yaml_nodes: Arc<Mutex<GarbageCollectedPool<serde_yaml::Value>>>,
+    pub yaml_nodes: Arc<Mutex<Store>>,
+    // The most recent decoding failure for each script, stored per-AMX so that
+    // gamemodes and filterscripts do not overwrite each other's errors.
+    pub parse_errors: HashMap<AmxIdent, ParseError>,
+    // The owner ID assigned to each loaded script. Nodes record the owner of
+    // the script that allocated them so they can be freed when it is unloaded.
+    pub owners: HashMap<AmxIdent, u32>,
+    pub next_owner: u32,
 }
 
-enum_from_primitive! {
-#[derive(Debug, PartialEq, Clone)]
-enum YamlNode {
-    Number = 0,
-    Boolean,
-    String,
-    Object,
-    Array,
-    Null,
-}
-}
+impl SampPlugin for Plugin {
+    fn on_amx_load(&mut self, amx: &Amx) {
+        self.next_owner += 1;
+        self.owners.insert(amx.ident(), self.next_owner);
+    }
 
-impl SampPlugin for Plugin {}
+    fn on_amx_unload(&mut self, amx: &Amx) {
+        self.parse_errors.remove(&amx.ident());
+
+        let owner = match self.owners.remove(&amx.ident()) {
+            Some(v) => v,
+            None => return,
+        };
+        let freed = self.yaml_nodes.lock().unwrap().free_owned(owner);
+        if freed > 0 {
+            info!("freed {} nodes left over by unloaded script", freed);
+        }
+    }
+}
 
 impl Plugin {
+    // store locks the node store for a native, so that any nodes it allocates
+    // are owned by the calling script and attributed to the native. In debug
+    // mode it also records the native and AMX so that stale handles can be
+    // traced back to the caller.
+    fn store(&self, amx: &Amx, native: &'static str) -> MutexGuard<'_, Store> {
+        let mut nodes = self.yaml_nodes.lock().unwrap();
+        nodes.set_owner(self.owners.get(&amx.ident()).copied().unwrap_or(0));
+        nodes.set_native(native);
+        if nodes.debug() {
+            nodes.set_caller(format!("{} (AMX {:?})", native, amx.ident()));
+        }
+        nodes
+    }
+
     #[native(name = "YAML_Parse")]
-    pub fn yaml_parse(&mut self, _: &Amx, input: AmxString, mut node: Ref<i32>) -> AmxResult<i32> {
-        let v: serde_yaml::Value = match serde_yaml::from_str(&input.to_string()) {
+    pub fn yaml_parse(
+        &mut self,
+        amx: &Amx,
+        input: AmxString,
+        mut node: Ref<i32>,
+        merge_keys: bool,
+        mut aliases: Ref<bool>,
+    ) -> AmxResult<i32> {
+        let result = self
+            .store(amx, "YAML_Parse")
+            .parse(&input.to_string(), merge_keys);
+        let (v, expanded) = match result {
             Ok(v) => v,
             Err(e) => {
-                error!("{}", e);
-                return Ok(1);
+                let code = e.code.code();
+                self.parse_errors.insert(amx.ident(), e);
+                return Ok(code);
             }
         };
-
-        let mut nodes = self.yaml_nodes.lock().unwrap();
-        *node = nodes.alloc(v);
+        self.parse_errors.remove(&amx.ident());
+        *node = v;
+        *aliases = expanded;
 
         Ok(0)
     }
@@ -45,25 +91,159 @@ impl Plugin {
     #[native(name = "YAML_Stringify")]
     pub fn yaml_stringify(
         &mut self,
-        _: &Amx,
+        amx: &Amx,
         node: i32,
         output: UnsizedBuffer,
         length: usize,
     ) -> AmxResult<i32> {
-        let mut nodes = self.yaml_nodes.lock().unwrap();
+        let mut nodes = self.store(amx, "YAML_Stringify");
+        let s = match nodes.stringify(node) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
+        };
 
-        let v: &serde_yaml::Value = match nodes.get(node) {
-            Some(v) => v,
-            None => return Ok(1),
+        let mut dest = output.into_sized_buffer(length);
+        let _ = samp::cell::string::put_in_buffer(&mut dest, &s);
+
+        Ok(0)
+    }
+
+    #[native(name = "YAML_ParseAll")]
+    pub fn yaml_parse_all(
+        &mut self,
+        amx: &Amx,
+        input: AmxString,
+        output: UnsizedBuffer,
+        max: usize,
+        mut count: Ref<i32>,
+    ) -> AmxResult<i32> {
+        let result = self
+            .store(amx, "YAML_ParseAll")
+            .parse_all(&input.to_string(), max);
+        let (docs, total) = match result {
+            Ok(v) => v,
+            Err(e) => {
+                let code = e.code.code();
+                self.parse_errors.insert(amx.ident(), e);
+                return Ok(code);
+            }
+        };
+        self.parse_errors.remove(&amx.ident());
+
+        let mut dest = output.into_sized_buffer(max);
+        for (i, node) in docs.iter().enumerate() {
+            dest[i] = *node;
+        }
+        *count = docs.len() as i32;
+
+        if total > max {
+            return Ok(YamlError::OutOfRange.code());
+        }
+        Ok(0)
+    }
+
+    #[native(name = "YAML_StringifyAll")]
+    pub fn yaml_stringify_all(
+        &mut self,
+        amx: &Amx,
+        input: UnsizedBuffer,
+        count: usize,
+        output: UnsizedBuffer,
+        length: usize,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_StringifyAll");
+        let input = input.into_sized_buffer(count);
+        let s = match nodes.stringify_all(&input) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
+        };
+
+        let mut dest = output.into_sized_buffer(length);
+        let _ = samp::cell::string::put_in_buffer(&mut dest, &s);
+
+        Ok(0)
+    }
+
+    #[native(name = "YAML_ParseJSON")]
+    pub fn yaml_parse_json(
+        &mut self,
+        amx: &Amx,
+        input: AmxString,
+        mut node: Ref<i32>,
+    ) -> AmxResult<i32> {
+        let result = self
+            .store(amx, "YAML_ParseJSON")
+            .parse_json(&input.to_string());
+        *node = match result {
+            Ok(v) => v,
+            Err(e) => {
+                let code = e.code.code();
+                self.parse_errors.insert(amx.ident(), e);
+                return Ok(code);
+            }
+        };
+        self.parse_errors.remove(&amx.ident());
+
+        Ok(0)
+    }
+
+    #[native(name = "YAML_StringifyJSON")]
+    pub fn yaml_stringify_json(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        output: UnsizedBuffer,
+        length: usize,
+        pretty: bool,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_StringifyJSON");
+        let s = match nodes.stringify_json(node, pretty) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
         };
 
-        let s = match serde_yaml::to_string(&v) {
+        let mut dest = output.into_sized_buffer(length);
+        let _ = samp::cell::string::put_in_buffer(&mut dest, &s);
+
+        Ok(0)
+    }
+
+    #[native(name = "YAML_ParseINI")]
+    pub fn yaml_parse_ini(
+        &mut self,
+        amx: &Amx,
+        input: AmxString,
+        mut node: Ref<i32>,
+    ) -> AmxResult<i32> {
+        let result = self
+            .store(amx, "YAML_ParseINI")
+            .parse_ini(&input.to_string());
+        *node = match result {
             Ok(v) => v,
             Err(e) => {
-                error!("{}", e);
-                return Ok(1);
+                let code = e.code.code();
+                self.parse_errors.insert(amx.ident(), e);
+                return Ok(code);
             }
         };
+        self.parse_errors.remove(&amx.ident());
+
+        Ok(0)
+    }
+
+    #[native(name = "YAML_StringifyINI")]
+    pub fn yaml_stringify_ini(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        output: UnsizedBuffer,
+        length: usize,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_StringifyINI");
+        let s = match nodes.stringify_ini(node) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
+        };
 
         let mut dest = output.into_sized_buffer(length);
         let _ = samp::cell::string::put_in_buffer(&mut dest, &s);
@@ -71,44 +251,171 @@ impl Plugin {
         Ok(0)
     }
 
-    #[native(name = "YAML_NodeType")]
-    pub fn yaml_node_type(&mut self, _: &Amx, node: i32) -> AmxResult<i32> {
-        let mut nodes = self.yaml_nodes.lock().unwrap();
-        let v: &serde_yaml::Value = match nodes.get(node) {
-            Some(v) => v,
-            None => &serde_yaml::Value::Null,
+    #[native(name = "YAML_ParseTOML")]
+    pub fn yaml_parse_toml(
+        &mut self,
+        amx: &Amx,
+        input: AmxString,
+        mut node: Ref<i32>,
+    ) -> AmxResult<i32> {
+        let result = self
+            .store(amx, "YAML_ParseTOML")
+            .parse_toml(&input.to_string());
+        *node = match result {
+            Ok(v) => v,
+            Err(e) => {
+                let code = e.code.code();
+                self.parse_errors.insert(amx.ident(), e);
+                return Ok(code);
+            }
+        };
+        self.parse_errors.remove(&amx.ident());
+
+        Ok(0)
+    }
+
+    #[native(name = "YAML_StringifyTOML")]
+    pub fn yaml_stringify_toml(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        output: UnsizedBuffer,
+        length: usize,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_StringifyTOML");
+        let s = match nodes.stringify_toml(node) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
+        };
+
+        let mut dest = output.into_sized_buffer(length);
+        let _ = samp::cell::string::put_in_buffer(&mut dest, &s);
+
+        Ok(0)
+    }
+
+    #[native(name = "YAML_ParseFile")]
+    pub fn yaml_parse_file(
+        &mut self,
+        amx: &Amx,
+        path: AmxString,
+        mut node: Ref<i32>,
+    ) -> AmxResult<i32> {
+        let result = self
+            .store(amx, "YAML_ParseFile")
+            .parse_file(&path.to_string());
+        *node = match result {
+            Ok(v) => v,
+            Err(e) => {
+                let code = e.code.code();
+                self.parse_errors.insert(amx.ident(), e);
+                return Ok(code);
+            }
+        };
+        self.parse_errors.remove(&amx.ident());
+
+        Ok(0)
+    }
+
+    #[native(name = "YAML_SaveFile")]
+    pub fn yaml_save_file(&mut self, amx: &Amx, node: i32, path: AmxString) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_SaveFile");
+        match nodes.save_file(node, &path.to_string()) {
+            Ok(_) => Ok(0),
+            Err(e) => Ok(e.code()),
+        }
+    }
+
+    #[native(name = "YAML_ParseRoundTrip")]
+    pub fn yaml_parse_round_trip(
+        &mut self,
+        amx: &Amx,
+        input: AmxString,
+        mut node: Ref<i32>,
+    ) -> AmxResult<i32> {
+        let result = self
+            .store(amx, "YAML_ParseRoundTrip")
+            .parse_round_trip(&input.to_string());
+        *node = match result {
+            Ok(v) => v,
+            Err(e) => {
+                let code = e.code.code();
+                self.parse_errors.insert(amx.ident(), e);
+                return Ok(code);
+            }
+        };
+        self.parse_errors.remove(&amx.ident());
+
+        Ok(0)
+    }
+
+    #[native(name = "YAML_ParseFileRoundTrip")]
+    pub fn yaml_parse_file_round_trip(
+        &mut self,
+        amx: &Amx,
+        path: AmxString,
+        mut node: Ref<i32>,
+    ) -> AmxResult<i32> {
+        let result = self
+            .store(amx, "YAML_ParseFileRoundTrip")
+            .parse_file_round_trip(&path.to_string());
+        *node = match result {
+            Ok(v) => v,
+            Err(e) => {
+                let code = e.code.code();
+                self.parse_errors.insert(amx.ident(), e);
+                return Ok(code);
+            }
         };
+        self.parse_errors.remove(&amx.ident());
 
-        debug!("{:?}", v);
+        Ok(0)
+    }
 
-        let t: i32 = match v {
-            serde_yaml::Value::Null => YamlNode::Null as i32,
-            serde_yaml::Value::Bool(_) => YamlNode::Boolean as i32,
-            serde_yaml::Value::Number(_) => YamlNode::Number as i32,
-            serde_yaml::Value::String(_) => YamlNode::String as i32,
-            serde_yaml::Value::Sequence(_) => YamlNode::Array as i32,
-            serde_yaml::Value::Mapping(_) => YamlNode::Object as i32,
+    #[native(name = "YAML_GetLastError")]
+    pub fn yaml_get_last_error(
+        &mut self,
+        amx: &Amx,
+        output: UnsizedBuffer,
+        length: usize,
+        mut line: Ref<i32>,
+        mut column: Ref<i32>,
+    ) -> AmxResult<i32> {
+        let e = match self.parse_errors.get(&amx.ident()) {
+            Some(v) => v,
+            None => return Ok(0),
         };
 
-        Ok(t)
+        let mut dest = output.into_sized_buffer(length);
+        let _ = samp::cell::string::put_in_buffer(&mut dest, &e.message);
+        *line = e.line;
+        *column = e.column;
+
+        Ok(e.code.code())
+    }
+
+    #[native(name = "YAML_NodeType")]
+    pub fn yaml_node_type(&mut self, amx: &Amx, node: i32) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_NodeType");
+        Ok(nodes.node_type(node) as i32)
     }
 
     #[native(raw, name = "YAML_Object")]
-    pub fn yaml_object(&mut self, _: &Amx, mut params: samp::args::Args) -> AmxResult<i32> {
+    pub fn yaml_object(&mut self, amx: &Amx, mut params: samp::args::Args) -> AmxResult<i32> {
         let arg_count = params.count();
         let pairs = if arg_count == 0 || arg_count % 2 == 0 {
             arg_count / 2
         } else {
             error!("invalid variadic argument pattern passed to YAML_Object");
-            return Ok(1);
+            return Ok(INVALID_NODE);
         };
 
-        let mut v = serde_yaml::Value::Mapping(serde_yaml::Mapping::new());
+        let mut entries = Vec::<(String, i32)>::new();
         for _ in 0..pairs {
             let key = match params.next::<AmxString>() {
                 None => {
                     error!("invalid type expected String");
-                    return Ok(2);
+                    return Ok(INVALID_NODE);
                 }
                 Some(parameter) => parameter,
             };
@@ -116,590 +423,1141 @@ impl Plugin {
             let node = match params.next::<Ref<i32>>() {
                 None => {
                     error!("invalid type expected int");
-                    return Ok(2);
+                    return Ok(INVALID_NODE);
                 }
                 Some(parameter) => parameter,
             };
 
-            let mut nodes = self.yaml_nodes.lock().unwrap();
-
-            let node = match nodes.take(*node) {
-                Some(v) => v,
-                None => {
-                    error!("invalid YAML node ID passed to YAML_Object");
-                    return Ok(2);
-                }
-            };
-
-            v[key.to_string()] = node.clone();
+            entries.push((key.to_string(), *node));
         }
 
-        let mut nodes = self.yaml_nodes.lock().unwrap();
-        Ok(nodes.alloc(v))
+        let mut nodes = self.store(amx, "YAML_Object");
+        Ok(nodes.object(entries).unwrap_or(INVALID_NODE))
     }
 
     #[native(name = "YAML_Int")]
-    pub fn yaml_int(&mut self, _: &Amx, value: i32) -> AmxResult<i32> {
-        let mut nodes = self.yaml_nodes.lock().unwrap();
-        Ok(nodes.alloc(serde_yaml::to_value(value).unwrap()))
+    pub fn yaml_int(&mut self, amx: &Amx, value: i32) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_Int");
+        Ok(nodes
+            .alloc(serde_yaml::to_value(value).unwrap())
+            .unwrap_or(INVALID_NODE))
     }
 
     #[native(name = "YAML_Bool")]
-    pub fn yaml_bool(&mut self, _: &Amx, value: bool) -> AmxResult<i32> {
-        let mut nodes = self.yaml_nodes.lock().unwrap();
-        Ok(nodes.alloc(serde_yaml::to_value(value).unwrap()))
+    pub fn yaml_bool(&mut self, amx: &Amx, value: bool) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_Bool");
+        Ok(nodes
+            .alloc(serde_yaml::to_value(value).unwrap())
+            .unwrap_or(INVALID_NODE))
     }
 
     #[native(name = "YAML_Float")]
-    pub fn yaml_float(&mut self, _: &Amx, value: f32) -> AmxResult<i32> {
-        let mut nodes = self.yaml_nodes.lock().unwrap();
-        Ok(nodes.alloc(serde_yaml::to_value(value).unwrap()))
+    pub fn yaml_float(&mut self, amx: &Amx, value: f32) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_Float");
+        Ok(nodes
+            .alloc(serde_yaml::to_value(value).unwrap())
+            .unwrap_or(INVALID_NODE))
     }
 
     #[native(name = "YAML_String")]
-    pub fn yaml_string(&mut self, _: &Amx, value: AmxString) -> AmxResult<i32> {
-        let mut nodes = self.yaml_nodes.lock().unwrap();
-        Ok(nodes.alloc(serde_yaml::to_value(value.to_string()).unwrap()))
+    pub fn yaml_string(&mut self, amx: &Amx, value: AmxString) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_String");
+        Ok(nodes
+            .alloc(serde_yaml::to_value(value.to_string()).unwrap())
+            .unwrap_or(INVALID_NODE))
     }
 
     #[native(raw, name = "YAML_Array")]
-    pub fn yaml_array(&mut self, _: &Amx, mut params: samp::args::Args) -> AmxResult<i32> {
+    pub fn yaml_array(&mut self, amx: &Amx, mut params: samp::args::Args) -> AmxResult<i32> {
         let args = params.count();
 
-        let mut arr = Vec::<serde_yaml::Value>::new();
+        let mut elements = Vec::<i32>::new();
         for _ in 0..args {
             let node = match params.next::<Ref<i32>>() {
                 None => {
                     error!("invalid type expected int");
-                    return Ok(1);
+                    return Ok(INVALID_NODE);
                 }
                 Some(parameter) => parameter,
             };
-
-            let mut nodes = self.yaml_nodes.lock().unwrap();
-            let node = match nodes.take(*node) {
-                Some(v) => v,
-                None => {
-                    error!("invalid YAML node ID passed to YAML_Array");
-                    return Ok(1);
-                }
-            };
-            arr.push(node.clone());
+            elements.push(*node);
         }
 
-        let mut nodes = self.yaml_nodes.lock().unwrap();
-
-        Ok(nodes.alloc(serde_yaml::Value::Sequence(arr)))
+        let mut nodes = self.store(amx, "YAML_Array");
+        Ok(nodes.array(elements).unwrap_or(INVALID_NODE))
     }
 
     #[native(name = "YAML_Append")]
-    pub fn yaml_append(&mut self, _: &Amx, a: i32, b: i32) -> AmxResult<i32> {
-        let mut nodes = self.yaml_nodes.lock().unwrap();
-
-        let a: serde_yaml::Value = match nodes.take(a) {
-            Some(v) => v,
-            None => return Ok(-1),
-        };
-        let b: serde_yaml::Value = match nodes.take(b) {
-            Some(v) => v,
-            None => return Ok(-1),
-        };
-
-        match (a.as_mapping(), b.as_mapping()) {
-            (Some(oa), Some(ob)) => {
-                let mut new = serde_yaml::Value::Mapping(serde_yaml::Mapping::new());
-                for (k, v) in oa.iter() {
-                    new.as_mapping_mut().unwrap().insert(k.clone(), v.clone());
-                }
-                for (k, v) in ob.iter() {
-                    new.as_mapping_mut().unwrap().insert(k.clone(), v.clone());
-                }
-                return Ok(nodes.alloc(new));
-            }
-            _ => debug!("append: a and b are not both objects"),
-        };
-
-        match (a.as_sequence(), b.as_sequence()) {
-            (Some(oa), Some(ob)) => {
-                let mut new = serde_yaml::Value::Sequence(Vec::new());
-                for v in oa.iter() {
-                    new.as_sequence_mut().unwrap().push(v.clone());
-                }
-                for v in ob.iter() {
-                    new.as_sequence_mut().unwrap().push(v.clone());
-                }
-                return Ok(nodes.alloc(new));
-            }
-            _ => debug!("append: a and b are not both arrays"),
-        };
-
-        debug!("failed to append: a and b are not both objects or arrays");
-
-        Ok(2)
+    pub fn yaml_append(&mut self, amx: &Amx, a: i32, b: i32) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_Append");
+        Ok(nodes.append(a, b).unwrap_or(INVALID_NODE))
     }
 
     #[native(name = "YAML_SetObject")]
     pub fn yaml_set_object(
         &mut self,
-        _: &Amx,
+        amx: &Amx,
         node: i32,
         key: AmxString,
         value: i32,
     ) -> AmxResult<i32> {
-        let mut nodes = self.yaml_nodes.lock().unwrap();
-
-        let src: serde_yaml::Value = match nodes.take(value) {
-            Some(v) => v.clone(),
-            None => return Ok(1),
-        };
-        let dst: &mut serde_yaml::Value = match nodes.get(node) {
-            Some(v) => v,
-            None => return Ok(1),
-        };
-        if !src.is_mapping() || !dst.is_mapping() {
-            return Ok(1);
+        let mut nodes = self.store(amx, "YAML_SetObject");
+        match nodes.set_object(node, &key.to_string(), value) {
+            Ok(_) => Ok(0),
+            Err(e) => Ok(e.code()),
         }
-
-        dst[key.to_string()] = src;
-        Ok(0)
     }
 
     #[native(name = "YAML_SetInt")]
     pub fn yaml_set_int(
         &mut self,
-        _: &Amx,
+        amx: &Amx,
         node: i32,
         key: AmxString,
         value: i32,
     ) -> AmxResult<i32> {
-        let mut nodes = self.yaml_nodes.lock().unwrap();
+        let mut nodes = self.store(amx, "YAML_SetInt");
+        let v = serde_yaml::to_value(value).unwrap();
+        match nodes.set(node, &key.to_string(), v) {
+            Ok(_) => Ok(0),
+            Err(e) => Ok(e.code()),
+        }
+    }
 
-        let v: &mut serde_yaml::Value = match nodes.get(node) {
-            Some(v) => v,
-            None => return Ok(1),
-        };
-        if !v.is_mapping() {
-            return Ok(1);
+    #[native(name = "YAML_SetInt64")]
+    pub fn yaml_set_int64(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        key: AmxString,
+        hi: i32,
+        lo: i32,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_SetInt64");
+        let v = ((hi as i64) << 32) | (lo as u32 as i64);
+        match nodes.set(node, &key.to_string(), v.into()) {
+            Ok(_) => Ok(0),
+            Err(e) => Ok(e.code()),
         }
+    }
 
-        v[key.to_string()] = serde_yaml::to_value(value).unwrap();
-        Ok(0)
+    #[native(name = "YAML_SetIntString")]
+    pub fn yaml_set_int_string(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        key: AmxString,
+        value: AmxString,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_SetIntString");
+        match nodes.set_int_string(node, &key.to_string(), &value.to_string()) {
+            Ok(_) => Ok(0),
+            Err(e) => Ok(e.code()),
+        }
     }
 
     #[native(name = "YAML_SetFloat")]
     pub fn yaml_set_float(
         &mut self,
-        _: &Amx,
+        amx: &Amx,
         node: i32,
         key: AmxString,
         value: f32,
     ) -> AmxResult<i32> {
-        let mut nodes = self.yaml_nodes.lock().unwrap();
-
-        let v: &mut serde_yaml::Value = match nodes.get(node) {
-            Some(v) => v,
-            None => return Ok(1),
-        };
-        if !v.is_mapping() {
-            return Ok(1);
+        let mut nodes = self.store(amx, "YAML_SetFloat");
+        let v = serde_yaml::to_value(value).unwrap();
+        match nodes.set(node, &key.to_string(), v) {
+            Ok(_) => Ok(0),
+            Err(e) => Ok(e.code()),
         }
-
-        v[key.to_string()] = serde_yaml::to_value(value).unwrap();
-        Ok(0)
     }
 
     #[native(name = "YAML_SetBool")]
     pub fn yaml_set_bool(
         &mut self,
-        _: &Amx,
+        amx: &Amx,
         node: i32,
         key: AmxString,
         value: bool,
     ) -> AmxResult<i32> {
-        let mut nodes = self.yaml_nodes.lock().unwrap();
-
-        let v: &mut serde_yaml::Value = match nodes.get(node) {
-            Some(v) => v,
-            None => return Ok(1),
-        };
-        if !v.is_mapping() {
-            return Ok(1);
+        let mut nodes = self.store(amx, "YAML_SetBool");
+        let v = serde_yaml::to_value(value).unwrap();
+        match nodes.set(node, &key.to_string(), v) {
+            Ok(_) => Ok(0),
+            Err(e) => Ok(e.code()),
         }
-
-        v[key.to_string()] = serde_yaml::to_value(value).unwrap();
-        Ok(0)
     }
 
     #[native(name = "YAML_SetString")]
     pub fn yaml_set_string(
         &mut self,
-        _: &Amx,
+        amx: &Amx,
         node: i32,
         key: AmxString,
         value: AmxString,
     ) -> AmxResult<i32> {
-        let mut nodes = self.yaml_nodes.lock().unwrap();
-
-        let v: &mut serde_yaml::Value = match nodes.get(node) {
-            Some(v) => v,
-            None => return Ok(1),
-        };
-        if !v.is_mapping() {
-            return Ok(1);
+        let mut nodes = self.store(amx, "YAML_SetString");
+        let v = serde_yaml::to_value(value.to_string()).unwrap();
+        match nodes.set(node, &key.to_string(), v) {
+            Ok(_) => Ok(0),
+            Err(e) => Ok(e.code()),
         }
-
-        v[key.to_string()] = serde_yaml::to_value(value.to_string()).unwrap();
-        Ok(0)
     }
 
-    #[native(name = "YAML_GetObject")]
-    pub fn yaml_get_object(
+    #[native(name = "YAML_SetPathNode")]
+    pub fn yaml_set_path_node(
         &mut self,
-        _: &Amx,
+        amx: &Amx,
         node: i32,
-        key: AmxString,
-        mut value: Ref<i32>,
+        path: AmxString,
+        value: i32,
     ) -> AmxResult<i32> {
-        let mut nodes = self.yaml_nodes.lock().unwrap();
-
-        let v: serde_yaml::Value = match nodes.get(node) {
-            Some(v) => v.clone(),
-            None => return Ok(1),
-        };
-        let v = match v.as_mapping() {
-            Some(v) => v,
-            None => return Ok(2),
-        };
-        let v = match v.get(&serde_yaml::Value::String(key.to_string())) {
-            Some(v) => v.clone(),
-            None => return Ok(3),
-        };
-        let v = nodes.alloc(v);
-        *value = v;
-
-        Ok(0)
+        let mut nodes = self.store(amx, "YAML_SetPathNode");
+        match nodes.set_path_node(node, &path.to_string(), value) {
+            Ok(_) => Ok(0),
+            Err(e) => Ok(e.code()),
+        }
     }
 
-    #[native(name = "YAML_GetInt")]
-    pub fn yaml_get_int(
+    #[native(name = "YAML_SetPathInt")]
+    pub fn yaml_set_path_int(
         &mut self,
-        _: &Amx,
+        amx: &Amx,
         node: i32,
-        key: AmxString,
-        mut value: Ref<i32>,
+        path: AmxString,
+        value: i32,
     ) -> AmxResult<i32> {
-        let mut nodes = self.yaml_nodes.lock().unwrap();
+        let mut nodes = self.store(amx, "YAML_SetPathInt");
+        let v = serde_yaml::to_value(value).unwrap();
+        match nodes.set_path(node, &path.to_string(), v) {
+            Ok(_) => Ok(0),
+            Err(e) => Ok(e.code()),
+        }
+    }
 
-        let v: serde_yaml::Value = match nodes.get(node) {
-            Some(v) => v.clone(),
-            None => return Ok(1),
-        };
-        let v = match v.as_mapping() {
-            Some(v) => v,
-            None => return Ok(1),
-        };
-        let v = match v.get(&serde_yaml::Value::String(key.to_string())) {
-            Some(v) => v.clone(),
-            None => return Ok(2),
+    #[native(name = "YAML_SetPathFloat")]
+    pub fn yaml_set_path_float(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        path: AmxString,
+        value: f32,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_SetPathFloat");
+        let v = serde_yaml::to_value(value).unwrap();
+        match nodes.set_path(node, &path.to_string(), v) {
+            Ok(_) => Ok(0),
+            Err(e) => Ok(e.code()),
+        }
+    }
+
+    #[native(name = "YAML_SetPathBool")]
+    pub fn yaml_set_path_bool(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        path: AmxString,
+        value: bool,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_SetPathBool");
+        let v = serde_yaml::to_value(value).unwrap();
+        match nodes.set_path(node, &path.to_string(), v) {
+            Ok(_) => Ok(0),
+            Err(e) => Ok(e.code()),
+        }
+    }
+
+    #[native(name = "YAML_SetPathString")]
+    pub fn yaml_set_path_string(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        path: AmxString,
+        value: AmxString,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_SetPathString");
+        let v = serde_yaml::to_value(value.to_string()).unwrap();
+        match nodes.set_path(node, &path.to_string(), v) {
+            Ok(_) => Ok(0),
+            Err(e) => Ok(e.code()),
+        }
+    }
+
+    #[native(name = "YAML_HasKey")]
+    pub fn yaml_has_key(&mut self, amx: &Amx, node: i32, key: AmxString) -> AmxResult<bool> {
+        let mut nodes = self.store(amx, "YAML_HasKey");
+        Ok(nodes.has_key(node, &key.to_string()))
+    }
+
+    #[native(name = "YAML_RemoveKey")]
+    pub fn yaml_remove_key(&mut self, amx: &Amx, node: i32, key: AmxString) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_RemoveKey");
+        match nodes.remove_key(node, &key.to_string()) {
+            Ok(_) => Ok(0),
+            Err(e) => Ok(e.code()),
+        }
+    }
+
+    #[native(name = "YAML_KeyType")]
+    pub fn yaml_key_type(&mut self, amx: &Amx, node: i32, key: AmxString) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_KeyType");
+        Ok(nodes.key_type(node, &key.to_string()) as i32)
+    }
+
+    #[native(name = "YAML_GetObject")]
+    pub fn yaml_get_object(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        key: AmxString,
+        mut value: Ref<i32>,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_GetObject");
+        *value = match nodes.get_object(node, &key.to_string()) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
         };
-        let v = match v.as_i64() {
-            Some(v) => v as i32,
-            None => return Ok(3),
+        Ok(0)
+    }
+
+    #[native(name = "YAML_GetObjectRef")]
+    pub fn yaml_get_object_ref(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        key: AmxString,
+        mut value: Ref<i32>,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_GetObjectRef");
+        *value = match nodes.get_object_ref(node, &key.to_string()) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
         };
-        *value = v;
+        Ok(0)
+    }
 
+    #[native(name = "YAML_GetInt")]
+    pub fn yaml_get_int(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        key: AmxString,
+        mut value: Ref<i32>,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_GetInt");
+        *value = match nodes.get_int(node, &key.to_string()) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
+        };
         Ok(0)
     }
 
     #[native(name = "YAML_GetFloat")]
     pub fn yaml_get_float(
         &mut self,
-        _: &Amx,
+        amx: &Amx,
         node: i32,
         key: AmxString,
         mut value: Ref<f32>,
     ) -> AmxResult<i32> {
-        let mut nodes = self.yaml_nodes.lock().unwrap();
-
-        let v: serde_yaml::Value = match nodes.get(node) {
-            Some(v) => v.clone(),
-            None => return Ok(1),
-        };
-        let v = match v.as_mapping() {
-            Some(v) => v,
-            None => return Ok(1),
-        };
-        let v = match v.get(&serde_yaml::Value::String(key.to_string())) {
-            Some(v) => v.clone(),
-            None => return Ok(2),
-        };
-        let v = match v.as_f64() {
-            Some(v) => v as f32,
-            None => return Ok(3),
+        let mut nodes = self.store(amx, "YAML_GetFloat");
+        *value = match nodes.get_float(node, &key.to_string()) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
         };
-
-        *value = v;
-
         Ok(0)
     }
 
     #[native(name = "YAML_GetBool")]
     pub fn yaml_get_bool(
         &mut self,
-        _: &Amx,
+        amx: &Amx,
         node: i32,
         key: AmxString,
         mut value: Ref<bool>,
     ) -> AmxResult<i32> {
-        let mut nodes = self.yaml_nodes.lock().unwrap();
-
-        let v: serde_yaml::Value = match nodes.get(node) {
-            Some(v) => v.clone(),
-            None => return Ok(1),
-        };
-        let v = match v.as_mapping() {
-            Some(v) => v,
-            None => return Ok(1),
-        };
-        let v = match v.get(&serde_yaml::Value::String(key.to_string())) {
-            Some(v) => v.clone(),
-            None => return Ok(2),
-        };
-        let v = match v.as_bool() {
-            Some(v) => v,
-            None => return Ok(3),
+        let mut nodes = self.store(amx, "YAML_GetBool");
+        *value = match nodes.get_bool(node, &key.to_string()) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
         };
-        *value = v;
         Ok(0)
     }
 
     #[native(name = "YAML_GetString")]
     pub fn yaml_get_string(
         &mut self,
-        _: &Amx,
+        amx: &Amx,
         node: i32,
         key: AmxString,
         value: UnsizedBuffer,
         length: usize,
     ) -> AmxResult<i32> {
-        let mut nodes = self.yaml_nodes.lock().unwrap();
+        let mut nodes = self.store(amx, "YAML_GetString");
+        let s = match nodes.get_string(node, &key.to_string()) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
+        };
+
+        let mut dest = value.into_sized_buffer(length);
+        let _ = samp::cell::string::put_in_buffer(&mut dest, &s);
+
+        Ok(0)
+    }
 
-        let v: serde_yaml::Value = match nodes.get(node) {
-            Some(v) => v.clone(),
-            None => return Ok(1),
+    #[native(name = "YAML_GetInt64")]
+    pub fn yaml_get_int64(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        key: AmxString,
+        mut hi: Ref<i32>,
+        mut lo: Ref<i32>,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_GetInt64");
+        let v = match nodes.get_int64(node, &key.to_string()) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
         };
-        let v = match v.as_mapping() {
-            Some(v) => v,
-            None => return Ok(1),
+        *hi = (v >> 32) as i32;
+        *lo = v as i32;
+        Ok(0)
+    }
+
+    #[native(name = "YAML_GetIntString")]
+    pub fn yaml_get_int_string(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        key: AmxString,
+        value: UnsizedBuffer,
+        length: usize,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_GetIntString");
+        let s = match nodes.get_int_string(node, &key.to_string()) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
         };
-        let v = match v.get(&serde_yaml::Value::String(key.to_string())) {
-            Some(v) => v.clone(),
-            None => return Ok(2),
+
+        let mut dest = value.into_sized_buffer(length);
+        let _ = samp::cell::string::put_in_buffer(&mut dest, &s);
+
+        Ok(0)
+    }
+
+    #[native(name = "YAML_GetIntLoose")]
+    pub fn yaml_get_int_loose(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        key: AmxString,
+        mut value: Ref<i32>,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_GetIntLoose");
+        *value = match nodes.get_int_loose(node, &key.to_string()) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
         };
-        let v = match v.as_str() {
-            Some(v) => v,
-            None => return Ok(3),
+        Ok(0)
+    }
+
+    #[native(name = "YAML_GetFloatLoose")]
+    pub fn yaml_get_float_loose(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        key: AmxString,
+        mut value: Ref<f32>,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_GetFloatLoose");
+        *value = match nodes.get_float_loose(node, &key.to_string()) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
+        };
+        Ok(0)
+    }
+
+    #[native(name = "YAML_GetBoolLoose")]
+    pub fn yaml_get_bool_loose(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        key: AmxString,
+        mut value: Ref<bool>,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_GetBoolLoose");
+        *value = match nodes.get_bool_loose(node, &key.to_string()) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
+        };
+        Ok(0)
+    }
+
+    #[native(name = "YAML_GetStringLoose")]
+    pub fn yaml_get_string_loose(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        key: AmxString,
+        value: UnsizedBuffer,
+        length: usize,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_GetStringLoose");
+        let s = match nodes.get_string_loose(node, &key.to_string()) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
         };
 
         let mut dest = value.into_sized_buffer(length);
-        let _ = samp::cell::string::put_in_buffer(&mut dest, &v);
+        let _ = samp::cell::string::put_in_buffer(&mut dest, &s);
 
         Ok(0)
     }
 
+    #[native(name = "YAML_GetIntOr")]
+    pub fn yaml_get_int_or(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        key: AmxString,
+        default: i32,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_GetIntOr");
+        Ok(nodes.get_int_or(node, &key.to_string(), default))
+    }
+
+    #[native(name = "YAML_GetFloatOr")]
+    pub fn yaml_get_float_or(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        key: AmxString,
+        default: f32,
+    ) -> AmxResult<f32> {
+        let mut nodes = self.store(amx, "YAML_GetFloatOr");
+        Ok(nodes.get_float_or(node, &key.to_string(), default))
+    }
+
+    #[native(name = "YAML_GetBoolOr")]
+    pub fn yaml_get_bool_or(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        key: AmxString,
+        default: bool,
+    ) -> AmxResult<bool> {
+        let mut nodes = self.store(amx, "YAML_GetBoolOr");
+        Ok(nodes.get_bool_or(node, &key.to_string(), default))
+    }
+
+    #[native(name = "YAML_GetStringOr")]
+    pub fn yaml_get_string_or(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        key: AmxString,
+        default: AmxString,
+        value: UnsizedBuffer,
+        length: usize,
+    ) -> AmxResult<bool> {
+        let mut nodes = self.store(amx, "YAML_GetStringOr");
+        let (s, found) = nodes.get_string_or(node, &key.to_string(), &default.to_string());
+
+        let mut dest = value.into_sized_buffer(length);
+        let _ = samp::cell::string::put_in_buffer(&mut dest, &s);
+
+        Ok(found)
+    }
+
     #[native(name = "YAML_GetArray")]
     pub fn yaml_get_array(
         &mut self,
-        _: &Amx,
+        amx: &Amx,
         node: i32,
         key: AmxString,
         mut value: Ref<i32>,
     ) -> AmxResult<i32> {
-        let mut nodes = self.yaml_nodes.lock().unwrap();
+        let mut nodes = self.store(amx, "YAML_GetArray");
+        *value = match nodes.get_array(node, &key.to_string()) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
+        };
+        Ok(0)
+    }
 
-        let v: serde_yaml::Value = match nodes.get(node) {
-            Some(v) => v.clone(),
-            None => return Ok(1),
+    #[native(name = "YAML_ArrayLength")]
+    pub fn yaml_array_length(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        mut length: Ref<i32>,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_ArrayLength");
+        *length = match nodes.array_length(node) {
+            Ok(v) => v as i32,
+            Err(e) => return Ok(e.code()),
         };
-        let v = match v.as_mapping() {
-            Some(v) => v,
-            None => return Ok(1),
+        Ok(0)
+    }
+
+    #[native(name = "YAML_ArrayObject")]
+    pub fn yaml_array_object(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        index: i32,
+        mut output: Ref<i32>,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_ArrayObject");
+        *output = match nodes.array_object(node, index) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
         };
-        let v = match v.get(&serde_yaml::Value::String(key.to_string())) {
-            Some(v) => v.clone(),
-            None => return Ok(2),
+        Ok(0)
+    }
+
+    #[native(name = "YAML_ArrayObjectRef")]
+    pub fn yaml_array_object_ref(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        index: i32,
+        mut output: Ref<i32>,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_ArrayObjectRef");
+        *output = match nodes.array_object_ref(node, index) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
         };
-        match v.as_sequence() {
-            Some(_) => (),
-            None => return Ok(3),
+        Ok(0)
+    }
+
+    #[native(name = "YAML_ArrayInt")]
+    pub fn yaml_array_int(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        index: i32,
+        mut output: Ref<i32>,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_ArrayInt");
+        *output = match nodes.array_int(node, index) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
         };
-        let v = nodes.alloc(v);
-        *value = v;
         Ok(0)
     }
 
-    #[native(name = "YAML_ArrayLength")]
-    pub fn yaml_array_length(
+    #[native(name = "YAML_ArrayFloat")]
+    pub fn yaml_array_float(
         &mut self,
-        _: &Amx,
+        amx: &Amx,
         node: i32,
-        mut length: Ref<i32>,
+        index: i32,
+        mut output: Ref<f32>,
     ) -> AmxResult<i32> {
-        let mut nodes = self.yaml_nodes.lock().unwrap();
+        let mut nodes = self.store(amx, "YAML_ArrayFloat");
+        *output = match nodes.array_float(node, index) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
+        };
+        Ok(0)
+    }
 
-        let v: serde_yaml::Value = match nodes.get(node) {
-            Some(v) => v.clone(),
-            None => return Ok(1),
+    #[native(name = "YAML_ArrayBool")]
+    pub fn yaml_array_bool(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        index: i32,
+        mut output: Ref<bool>,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_ArrayBool");
+        *output = match nodes.array_bool(node, index) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
         };
-        let v = match v.as_sequence() {
-            Some(v) => v,
-            None => return Ok(1),
+        Ok(0)
+    }
+
+    #[native(name = "YAML_ArrayString")]
+    pub fn yaml_array_string(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        index: i32,
+        output: UnsizedBuffer,
+        length: usize,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_ArrayString");
+        let s = match nodes.array_string(node, index) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
         };
-        *length = v.len() as i32;
+
+        let mut dest = output.into_sized_buffer(length);
+        let _ = samp::cell::string::put_in_buffer(&mut dest, &s);
+
         Ok(0)
     }
 
-    #[native(name = "YAML_ArrayObject")]
-    pub fn yaml_array_object(
+    #[native(name = "YAML_ArrayToInts")]
+    pub fn yaml_array_to_ints(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        output: UnsizedBuffer,
+        max: usize,
+        mut count: Ref<i32>,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_ArrayToInts");
+        let mut values = Vec::<i32>::new();
+        let result = nodes.array_to_ints(node, max, &mut values);
+
+        let mut dest = output.into_sized_buffer(max);
+        for (i, v) in values.iter().enumerate() {
+            dest[i] = *v;
+        }
+        *count = values.len() as i32;
+
+        match result {
+            Ok(_) => Ok(0),
+            Err(e) => Ok(e.code()),
+        }
+    }
+
+    #[native(name = "YAML_ArrayToFloats")]
+    pub fn yaml_array_to_floats(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        output: UnsizedBuffer,
+        max: usize,
+        mut count: Ref<i32>,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_ArrayToFloats");
+        let mut values = Vec::<f32>::new();
+        let result = nodes.array_to_floats(node, max, &mut values);
+
+        let mut dest = output.into_sized_buffer(max);
+        for (i, v) in values.iter().enumerate() {
+            dest[i] = v.to_bits() as i32;
+        }
+        *count = values.len() as i32;
+
+        match result {
+            Ok(_) => Ok(0),
+            Err(e) => Ok(e.code()),
+        }
+    }
+
+    #[native(name = "YAML_ArrayPush")]
+    pub fn yaml_array_push(&mut self, amx: &Amx, node: i32, value: i32) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_ArrayPush");
+        match nodes.array_push(node, value) {
+            Ok(_) => Ok(0),
+            Err(e) => Ok(e.code()),
+        }
+    }
+
+    #[native(name = "YAML_ArrayInsert")]
+    pub fn yaml_array_insert(
         &mut self,
-        _: &Amx,
+        amx: &Amx,
+        node: i32,
+        index: i32,
+        value: i32,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_ArrayInsert");
+        match nodes.array_insert(node, index, value) {
+            Ok(_) => Ok(0),
+            Err(e) => Ok(e.code()),
+        }
+    }
+
+    #[native(name = "YAML_ArrayRemove")]
+    pub fn yaml_array_remove(&mut self, amx: &Amx, node: i32, index: i32) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_ArrayRemove");
+        match nodes.array_remove(node, index) {
+            Ok(_) => Ok(0),
+            Err(e) => Ok(e.code()),
+        }
+    }
+
+    #[native(name = "YAML_ArraySet")]
+    pub fn yaml_array_set(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        index: i32,
+        value: i32,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_ArraySet");
+        match nodes.array_set(node, index, value) {
+            Ok(_) => Ok(0),
+            Err(e) => Ok(e.code()),
+        }
+    }
+
+    #[native(name = "YAML_ArrayPushInt")]
+    pub fn yaml_array_push_int(&mut self, amx: &Amx, node: i32, value: i32) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_ArrayPushInt");
+        let v = serde_yaml::to_value(value).unwrap();
+        match nodes.array_push_value(node, v) {
+            Ok(_) => Ok(0),
+            Err(e) => Ok(e.code()),
+        }
+    }
+
+    #[native(name = "YAML_ArrayPushFloat")]
+    pub fn yaml_array_push_float(&mut self, amx: &Amx, node: i32, value: f32) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_ArrayPushFloat");
+        let v = serde_yaml::to_value(value).unwrap();
+        match nodes.array_push_value(node, v) {
+            Ok(_) => Ok(0),
+            Err(e) => Ok(e.code()),
+        }
+    }
+
+    #[native(name = "YAML_ArrayPushBool")]
+    pub fn yaml_array_push_bool(&mut self, amx: &Amx, node: i32, value: bool) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_ArrayPushBool");
+        let v = serde_yaml::to_value(value).unwrap();
+        match nodes.array_push_value(node, v) {
+            Ok(_) => Ok(0),
+            Err(e) => Ok(e.code()),
+        }
+    }
+
+    #[native(name = "YAML_ArrayPushString")]
+    pub fn yaml_array_push_string(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        value: AmxString,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_ArrayPushString");
+        let v = serde_yaml::to_value(value.to_string()).unwrap();
+        match nodes.array_push_value(node, v) {
+            Ok(_) => Ok(0),
+            Err(e) => Ok(e.code()),
+        }
+    }
+
+    #[native(name = "YAML_ObjectLength")]
+    pub fn yaml_object_length(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        mut length: Ref<i32>,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_ObjectLength");
+        *length = match nodes.object_length(node) {
+            Ok(v) => v as i32,
+            Err(e) => return Ok(e.code()),
+        };
+        Ok(0)
+    }
+
+    #[native(name = "YAML_ObjectKey")]
+    pub fn yaml_object_key(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        index: i32,
+        output: UnsizedBuffer,
+        length: usize,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_ObjectKey");
+        let s = match nodes.object_key(node, index) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
+        };
+
+        let mut dest = output.into_sized_buffer(length);
+        let _ = samp::cell::string::put_in_buffer(&mut dest, &s);
+
+        Ok(0)
+    }
+
+    #[native(name = "YAML_ObjectValue")]
+    pub fn yaml_object_value(
+        &mut self,
+        amx: &Amx,
         node: i32,
         index: i32,
         mut output: Ref<i32>,
     ) -> AmxResult<i32> {
-        let mut nodes = self.yaml_nodes.lock().unwrap();
+        let mut nodes = self.store(amx, "YAML_ObjectValue");
+        *output = match nodes.object_value(node, index) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
+        };
+        Ok(0)
+    }
 
-        let v: serde_yaml::Value = match nodes.get(node) {
-            Some(v) => v.clone(),
-            None => return Ok(1),
+    #[native(name = "YAML_GetPathInt")]
+    pub fn yaml_get_path_int(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        path: AmxString,
+        mut value: Ref<i32>,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_GetPathInt");
+        *value = match nodes.get_path_int(node, &path.to_string()) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
         };
-        let v = match v.as_sequence() {
-            Some(v) => v,
-            None => return Ok(1),
+        Ok(0)
+    }
+
+    #[native(name = "YAML_GetPathFloat")]
+    pub fn yaml_get_path_float(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        path: AmxString,
+        mut value: Ref<f32>,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_GetPathFloat");
+        *value = match nodes.get_path_float(node, &path.to_string()) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
         };
-        let v = match v.get(index as usize) {
-            Some(v) => v.clone(),
-            None => return Ok(2),
+        Ok(0)
+    }
+
+    #[native(name = "YAML_GetPathBool")]
+    pub fn yaml_get_path_bool(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        path: AmxString,
+        mut value: Ref<bool>,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_GetPathBool");
+        *value = match nodes.get_path_bool(node, &path.to_string()) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
+        };
+        Ok(0)
+    }
+
+    #[native(name = "YAML_GetPathString")]
+    pub fn yaml_get_path_string(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        path: AmxString,
+        value: UnsizedBuffer,
+        length: usize,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_GetPathString");
+        let s = match nodes.get_path_string(node, &path.to_string()) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
+        };
+
+        let mut dest = value.into_sized_buffer(length);
+        let _ = samp::cell::string::put_in_buffer(&mut dest, &s);
+
+        Ok(0)
+    }
+
+    #[native(name = "YAML_GetPathNode")]
+    pub fn yaml_get_path_node(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        path: AmxString,
+        mut value: Ref<i32>,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_GetPathNode");
+        *value = match nodes.get_path_node(node, &path.to_string()) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
         };
-        let v = nodes.alloc(v);
-        *output = v;
         Ok(0)
     }
 
     #[native(name = "YAML_GetNodeInt")]
     pub fn yaml_get_node_int(
         &mut self,
-        _: &Amx,
+        amx: &Amx,
         node: i32,
         mut output: Ref<i32>,
     ) -> AmxResult<i32> {
-        let mut nodes = self.yaml_nodes.lock().unwrap();
-
-        let v: serde_yaml::Value = match nodes.take(node) {
-            Some(v) => v.clone(),
-            None => return Ok(1),
-        };
-        let v = match v.as_i64() {
-            Some(v) => v as i32,
-            None => return Ok(1),
+        let mut nodes = self.store(amx, "YAML_GetNodeInt");
+        *output = match nodes.get_node_int(node) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
         };
-        *output = v;
         Ok(0)
     }
 
     #[native(name = "YAML_GetNodeFloat")]
     pub fn yaml_get_node_float(
         &mut self,
-        _: &Amx,
+        amx: &Amx,
         node: i32,
         mut output: Ref<f32>,
     ) -> AmxResult<i32> {
-        let mut nodes = self.yaml_nodes.lock().unwrap();
-
-        let v: serde_yaml::Value = match nodes.take(node) {
-            Some(v) => v.clone(),
-            None => return Ok(1),
-        };
-        let v = match v.as_f64() {
-            Some(v) => v as f32,
-            None => return Ok(1),
+        let mut nodes = self.store(amx, "YAML_GetNodeFloat");
+        *output = match nodes.get_node_float(node) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
         };
-        *output = v;
         Ok(0)
     }
 
     #[native(name = "YAML_GetNodeBool")]
     pub fn yaml_get_node_bool(
         &mut self,
-        _: &Amx,
+        amx: &Amx,
         node: i32,
         mut output: Ref<bool>,
     ) -> AmxResult<i32> {
-        let mut nodes = self.yaml_nodes.lock().unwrap();
-
-        let v: serde_yaml::Value = match nodes.take(node) {
-            Some(v) => v.clone(),
-            None => return Ok(1),
-        };
-        let v = match v.as_bool() {
-            Some(v) => v,
-            None => return Ok(1),
+        let mut nodes = self.store(amx, "YAML_GetNodeBool");
+        *output = match nodes.get_node_bool(node) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
         };
-        *output = v;
         Ok(0)
     }
 
     #[native(name = "YAML_GetNodeString")]
     pub fn yaml_get_node_string(
         &mut self,
-        _: &Amx,
+        amx: &Amx,
         node: i32,
         output: UnsizedBuffer,
         length: usize,
     ) -> AmxResult<i32> {
-        let mut nodes = self.yaml_nodes.lock().unwrap();
-
-        let v: serde_yaml::Value = match nodes.take(node) {
-            Some(v) => v.clone(),
-            None => {
-                debug!("value under {} doesn't exist", node);
-                return Ok(1);
-            }
+        let mut nodes = self.store(amx, "YAML_GetNodeString");
+        let s = match nodes.get_node_string(node) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
         };
-        let v = match v.as_str() {
-            Some(v) => v,
-            None => {
-                debug!("value is not a string {:?}", v);
-                return Ok(1);
-            }
+
+        let mut dest = output.into_sized_buffer(length);
+        let _ = samp::cell::string::put_in_buffer(&mut dest, &s);
+
+        Ok(0)
+    }
+
+    #[native(name = "YAML_Pack")]
+    pub fn yaml_pack(
+        &mut self,
+        amx: &Amx,
+        format: AmxString,
+        input: UnsizedBuffer,
+        length: usize,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_Pack");
+        let input = input.into_sized_buffer(length);
+        Ok(nodes
+            .pack(&format.to_string(), &input)
+            .unwrap_or(INVALID_NODE))
+    }
+
+    #[native(name = "YAML_Unpack")]
+    pub fn yaml_unpack(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        format: AmxString,
+        output: UnsizedBuffer,
+        length: usize,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_Unpack");
+        let mut dest = output.into_sized_buffer(length);
+        match nodes.unpack(node, &format.to_string(), &mut dest) {
+            Ok(_) => Ok(0),
+            Err(e) => Ok(e.code()),
+        }
+    }
+
+    #[native(name = "YAML_Validate")]
+    pub fn yaml_validate(
+        &mut self,
+        amx: &Amx,
+        node: i32,
+        schema: i32,
+        output: UnsizedBuffer,
+        length: usize,
+        mut count: Ref<i32>,
+    ) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_Validate");
+        let errors = match nodes.validate(node, schema) {
+            Ok(v) => v,
+            Err(e) => return Ok(e.code()),
         };
+
         let mut dest = output.into_sized_buffer(length);
-        let _ = samp::cell::string::put_in_buffer(&mut dest, &v);
+        let _ = samp::cell::string::put_in_buffer(&mut dest, &errors.join("\n"));
+        *count = errors.len() as i32;
 
+        if !errors.is_empty() {
+            return Ok(YamlError::Validation.code());
+        }
         Ok(0)
     }
 
     #[native(name = "YAML_ToggleGC")]
-    pub fn yaml_toggle_gc(&mut self, _: &Amx, node: i32, set: bool) -> AmxResult<i32> {
+    pub fn yaml_toggle_gc(&mut self, amx: &Amx, node: i32, set: bool) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_ToggleGC");
+        match nodes.toggle_gc(node, set) {
+            Ok(_) => Ok(0),
+            Err(e) => Ok(e.code()),
+        }
+    }
+
+    #[native(name = "YAML_ToggleDebug")]
+    pub fn yaml_toggle_debug(&mut self, _: &Amx, toggle: bool) -> AmxResult<i32> {
         let mut nodes = self.yaml_nodes.lock().unwrap();
+        nodes.set_debug(toggle);
+        Ok(0)
+    }
 
-        match nodes.set_gc(node, set) {
-            Some(_) => Ok(0),
-            None => Ok(1),
+    #[native(name = "YAML_ToggleLooseTypes")]
+    pub fn yaml_toggle_loose_types(&mut self, _: &Amx, toggle: bool) -> AmxResult<i32> {
+        let mut nodes = self.yaml_nodes.lock().unwrap();
+        nodes.set_loose(toggle);
+        Ok(0)
+    }
+
+    #[native(name = "YAML_PoolStats")]
+    pub fn yaml_pool_stats(
+        &mut self,
+        amx: &Amx,
+        mut live: Ref<i32>,
+        mut gc_disabled: Ref<i32>,
+        mut bytes: Ref<i32>,
+    ) -> AmxResult<i32> {
+        let nodes = self.store(amx, "YAML_PoolStats");
+        let stats = nodes.stats();
+        *live = stats.live as i32;
+        *gc_disabled = stats.gc_disabled as i32;
+        *bytes = stats.bytes.min(i32::MAX as usize) as i32;
+        Ok(0)
+    }
+
+    #[native(name = "YAML_DumpLiveNodes")]
+    pub fn yaml_dump_live_nodes(&mut self, amx: &Amx, path: AmxString) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_DumpLiveNodes");
+        match nodes.dump_live_nodes(&path.to_string()) {
+            Ok(_) => Ok(0),
+            Err(e) => Ok(e.code()),
         }
     }
 
     #[native(name = "YAML_Cleanup")]
-    pub fn yaml_cleanup(&mut self, _: &Amx, node: i32, auto: bool) -> AmxResult<i32> {
-        let mut nodes = self.yaml_nodes.lock().unwrap();
-
-        match if auto {
-            nodes.collect(node)
-        } else {
-            nodes.collect_force(node)
-        } {
-            Some(_) => Ok(0),
-            None => Ok(1),
+    pub fn yaml_cleanup(&mut self, amx: &Amx, node: i32, auto: bool) -> AmxResult<i32> {
+        let mut nodes = self.store(amx, "YAML_Cleanup");
+        match nodes.cleanup(node, auto) {
+            Ok(_) => Ok(0),
+            Err(e) => Ok(e.code()),
         }
     }
 }
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
 pub struct Pool<T> {
     pub active: HashMap<i32, T>,
@@ -14,63 +14,226 @@ impl<T> Default for Pool<T> {
     }
 }
 
+// Handles encode a slot index in the low bits and the slot's generation in the
+// bits above it. A slot's generation is bumped each time its item is freed, so
+// a handle kept after its node was consumed or cleaned up no longer matches the
+// slot once the slot is reused. Generations start at 1 and skip 0 when they
+// wrap so handles are always positive and never 0.
+//
+// Handles only have room for GENERATION_MAX generations, so a slot that has
+// been reused that many times matches old handles again. To push that point as
+// far out as possible, freed slots are reused in the order they were freed and
+// only once more than REUSE_DELAY slots are free, unless every slot is in use.
+// A stale handle can therefore only match a new node after at least
+// GENERATION_MAX * REUSE_DELAY, around 8 million, further allocations.
+const INDEX_BITS: u32 = 20;
+const INDEX_MASK: i32 = (1 << INDEX_BITS) - 1;
+pub const GENERATION_MAX: u32 = (1 << (31 - INDEX_BITS)) - 1;
+const REUSE_DELAY: usize = 4096;
+
 pub struct GarbageCollectedPool<T: Clone> {
-    pub active: HashMap<i32, Item<T>>,
-    current: i32,
+    slots: Vec<Slot<T>>,
+    free: VecDeque<usize>,
+    // The most items that can be live at once.
+    capacity: usize,
+    live: usize,
+}
+
+struct Slot<T: Clone> {
+    generation: u32,
+    item: Option<Item<T>>,
 }
 
 #[derive(Clone)]
 pub struct Item<T: Clone> {
     pub value: T,
     pub gc: bool,
+    // The script that allocated the item, 0 if it has no owner.
+    pub owner: u32,
+    // The native that allocated the item, for leak reports.
+    pub origin: &'static str,
+}
+
+// Handle describes what a handle refers to, used to tell stale handles apart
+// from values that were never handed out.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Handle {
+    Live,
+    // The slot exists but has been freed or reused since the handle was issued.
+    // Holds the slot index and the generation the slot is now at.
+    Stale { index: usize, generation: u32 },
+    Invalid,
 }
 
 impl<T: Clone> Default for GarbageCollectedPool<T> {
     fn default() -> Self {
-        GarbageCollectedPool {
-            active: HashMap::new(),
-            current: 0,
-        }
+        GarbageCollectedPool::with_capacity(INDEX_MASK as usize + 1)
     }
 }
 
 impl<T: Clone> GarbageCollectedPool<T> {
-    pub fn alloc(&mut self, t: T) -> i32 {
-        self.current += 1;
-        self.active
-            .insert(self.current, Item::<T> { value: t, gc: true });
-        self.current
+    // with_capacity creates a pool holding at most `capacity` live items, which
+    // is limited to the number of slots a handle can address.
+    pub fn with_capacity(capacity: usize) -> Self {
+        GarbageCollectedPool {
+            slots: Vec::new(),
+            free: VecDeque::new(),
+            live: 0,
+            capacity: capacity.min(INDEX_MASK as usize + 1),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // live returns the number of items currently allocated.
+    pub fn live(&self) -> usize {
+        self.live
+    }
+
+    // alloc returns None when every slot is in use.
+    pub fn alloc(&mut self, t: T, owner: u32, origin: &'static str) -> Option<i32> {
+        let index = if self.free.len() > REUSE_DELAY || self.slots.len() >= self.capacity {
+            self.free.pop_front()?
+        } else {
+            self.slots.push(Slot {
+                generation: 1,
+                item: None,
+            });
+            self.slots.len() - 1
+        };
+        let slot = &mut self.slots[index];
+        self.live += 1;
+        slot.item = Some(Item::<T> {
+            value: t,
+            gc: true,
+            owner,
+            origin,
+        });
+        Some(encode(index, slot.generation))
     }
 
     pub fn get(&mut self, id: i32) -> Option<&mut T> {
-        let i: &mut Item<T> = self.active.get_mut(&id)?;
+        let i: &mut Item<T> = self.item(id)?;
         Some(&mut i.value)
     }
 
+    pub fn peek(&self, id: i32) -> Option<&T> {
+        let (index, generation) = decode(id)?;
+        let slot = self.slots.get(index)?;
+        if slot.generation != generation {
+            return None;
+        }
+        slot.item.as_ref().map(|i| &i.value)
+    }
+
     pub fn take(&mut self, id: i32) -> Option<T> {
-        let i = match self.active.get(&id) {
-            Some(v) => v.clone(),
-            None => return None,
-        };
+        let i = self.item(id)?.clone();
         if i.gc {
-            self.active.remove(&id)?;
+            self.remove(id)?;
         }
         Some(i.value)
     }
 
     pub fn set_gc(&mut self, id: i32, set: bool) -> Option<()> {
-        self.active.get_mut(&id)?.gc = set;
+        self.item(id)?.gc = set;
         Some(())
     }
 
     pub fn collect(&mut self, id: i32) -> Option<Item<T>> {
-        if !self.active.get(&id)?.gc {
+        if !self.item(id)?.gc {
             return None;
         }
-        self.active.remove(&id)
+        self.remove(id)
     }
 
     pub fn collect_force(&mut self, id: i32) -> Option<Item<T>> {
-        self.active.remove(&id)
+        self.remove(id)
+    }
+
+    // collect_owned frees every item allocated by `owner`, regardless of its
+    // garbage collection flag, and returns how many were freed.
+    pub fn collect_owned(&mut self, owner: u32) -> usize {
+        let mut freed = 0;
+        for index in 0..self.slots.len() {
+            let slot = &self.slots[index];
+            match &slot.item {
+                Some(i) if i.owner == owner => (),
+                _ => continue,
+            }
+            if self.remove(encode(index, slot.generation)).is_some() {
+                freed += 1;
+            }
+        }
+        freed
+    }
+
+    // iter visits every live item along with its handle, in slot order.
+    pub fn iter(&self) -> impl Iterator<Item = (i32, &Item<T>)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            let item = slot.item.as_ref()?;
+            Some((encode(index, slot.generation), item))
+        })
+    }
+
+    pub fn handle(&self, id: i32) -> Handle {
+        let (index, generation) = match decode(id) {
+            Some(v) => v,
+            None => return Handle::Invalid,
+        };
+        let slot = match self.slots.get(index) {
+            Some(v) => v,
+            None => return Handle::Invalid,
+        };
+        if slot.generation == generation && slot.item.is_some() {
+            Handle::Live
+        } else {
+            Handle::Stale {
+                index,
+                generation: slot.generation,
+            }
+        }
+    }
+
+    fn item(&mut self, id: i32) -> Option<&mut Item<T>> {
+        let (index, generation) = decode(id)?;
+        let slot = self.slots.get_mut(index)?;
+        if slot.generation != generation {
+            return None;
+        }
+        slot.item.as_mut()
+    }
+
+    fn remove(&mut self, id: i32) -> Option<Item<T>> {
+        let (index, generation) = decode(id)?;
+        let slot = self.slots.get_mut(index)?;
+        if slot.generation != generation {
+            return None;
+        }
+        let item = slot.item.take()?;
+        slot.generation = if slot.generation == GENERATION_MAX {
+            1
+        } else {
+            slot.generation + 1
+        };
+        self.free.push_back(index);
+        self.live -= 1;
+        Some(item)
+    }
+}
+
+fn encode(index: usize, generation: u32) -> i32 {
+    ((generation as i32) << INDEX_BITS) | index as i32
+}
+
+fn decode(id: i32) -> Option<(usize, u32)> {
+    if id <= 0 {
+        return None;
+    }
+    let generation = (id >> INDEX_BITS) as u32;
+    if generation == 0 {
+        return None;
     }
+    Some(((id & INDEX_MASK) as usize, generation))
 }
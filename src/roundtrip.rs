@@ -0,0 +1,578 @@
+use serde_yaml::Value;
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::{Marker, TScalarStyle};
+
+// Document is the source text a round-trip node was parsed from, along with
+// the value it decoded to and where each part of that value sits in the text.
+// Rendering a node with a document diffs the node's current value against the
+// original and patches only the parts of the text that changed, so comments,
+// blank lines, key order, anchors and quoting survive being edited in-game.
+pub struct Document {
+    source: String,
+    original: Value,
+    root: Span,
+}
+
+// Span is the position of a value in the source text, in bytes. `end` is None
+// where it could not be worked out, such as for multi-line plain scalars, and
+// any edit needing it falls back to rendering the whole document.
+struct Span {
+    start: usize,
+    end: Option<usize>,
+    // Anchored values may be aliased elsewhere in the document, so changing
+    // them in place would also change every alias.
+    anchored: bool,
+    shape: Shape,
+}
+
+enum Shape {
+    Scalar {
+        style: TScalarStyle,
+        tagged: bool,
+    },
+    // A value left out entirely, such as `key:`, which decodes to null.
+    Empty,
+    Alias,
+    Map {
+        flow: bool,
+        entries: Vec<(Span, Span)>,
+    },
+    Seq {
+        flow: bool,
+        items: Vec<Span>,
+    },
+}
+
+impl Document {
+    // parse records the layout of `source`, which must already have decoded to
+    // `original`. Returns None if the layout could not be recorded.
+    pub fn parse(source: &str, original: Value) -> Option<Document> {
+        let mut builder = Builder {
+            source,
+            offsets: source
+                .char_indices()
+                .map(|(i, _)| i)
+                .chain(std::iter::once(source.len()))
+                .collect(),
+            stack: Vec::new(),
+            root: None,
+        };
+        Parser::new(source.chars()).load(&mut builder, false).ok()?;
+        Some(Document {
+            source: source.to_string(),
+            original,
+            root: builder.root?,
+        })
+    }
+
+    // render returns the source text patched so that it decodes to `current`.
+    // Returns None if the changes cannot be applied as a patch, or if the
+    // patched text does not decode to `current`, in which case the value should
+    // be encoded from scratch.
+    pub fn render(&self, current: &Value) -> Option<String> {
+        let mut patch = Patch {
+            source: &self.source,
+            edits: Vec::new(),
+            failed: false,
+        };
+        if !patch.diff(&self.root, &self.original, current) || patch.failed {
+            return None;
+        }
+
+        // Edits never overlap, so applying them from the end of the text
+        // backwards keeps the offsets of the remaining ones valid. Insertions
+        // at the same point are applied in reverse so they appear in order.
+        let mut edits: Vec<_> = patch.edits.into_iter().enumerate().collect();
+        edits.sort_by(|(i, a), (j, b)| (b.0, j).cmp(&(a.0, i)));
+        let mut out = self.source.clone();
+        for (_, (start, end, text)) in edits {
+            out.replace_range(start..end, &text);
+        }
+
+        // Spans are worked out from parser events, which don't describe every
+        // detail of the text, such as trailing lines kept by `|+` scalars. A
+        // patch that changes more than intended is discarded.
+        match serde_yaml::from_str::<Value>(&out) {
+            Ok(v) if v == *current => Some(out),
+            _ => None,
+        }
+    }
+}
+
+// -
+// Recording the layout
+// -
+
+struct Builder<'a> {
+    source: &'a str,
+    // Markers count characters, offsets maps them to byte positions.
+    offsets: Vec<usize>,
+    stack: Vec<Frame>,
+    root: Option<Span>,
+}
+
+struct Frame {
+    start: usize,
+    anchored: bool,
+    flow: bool,
+    map: bool,
+    children: Vec<Span>,
+}
+
+impl<'a> MarkedEventReceiver for Builder<'a> {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        let pos = self.offsets.get(mark.index()).copied().unwrap_or(0);
+        match ev {
+            Event::Scalar(value, style, anchor, tag) => {
+                let span = self.scalar(pos, &value, style, anchor != 0, tag.is_some());
+                self.push(span);
+            }
+            Event::Alias(_) => {
+                let rest = &self.source[pos..];
+                let len = rest
+                    .find(|c: char| c.is_whitespace() || ",[]{}".contains(c))
+                    .unwrap_or(rest.len());
+                self.push(Span {
+                    start: pos,
+                    end: Some(pos + len),
+                    anchored: false,
+                    shape: Shape::Alias,
+                });
+            }
+            Event::MappingStart(anchor) => self.stack.push(Frame {
+                start: pos,
+                anchored: anchor != 0,
+                flow: self.source[pos..].starts_with('{'),
+                map: true,
+                children: Vec::new(),
+            }),
+            Event::SequenceStart(anchor) => self.stack.push(Frame {
+                start: pos,
+                anchored: anchor != 0,
+                flow: self.source[pos..].starts_with('['),
+                map: false,
+                children: Vec::new(),
+            }),
+            Event::MappingEnd | Event::SequenceEnd => {
+                if let Some(frame) = self.stack.pop() {
+                    let span = self.collection(frame, pos);
+                    self.push(span);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+impl<'a> Builder<'a> {
+    fn push(&mut self, span: Span) {
+        match self.stack.last_mut() {
+            Some(frame) => frame.children.push(span),
+            None => self.root = Some(span),
+        }
+    }
+
+    fn scalar(
+        &self,
+        start: usize,
+        value: &str,
+        style: TScalarStyle,
+        anchored: bool,
+        tagged: bool,
+    ) -> Span {
+        let rest = &self.source[start..];
+        let end = match style {
+            TScalarStyle::Plain => {
+                if value == "~" && !rest.starts_with('~') {
+                    // The marker of an empty value points at whatever follows
+                    // it, its position is filled in by the enclosing mapping.
+                    return Span {
+                        start,
+                        end: None,
+                        anchored,
+                        shape: Shape::Empty,
+                    };
+                }
+                if rest.starts_with(value) {
+                    Some(start + value.len())
+                } else {
+                    None
+                }
+            }
+            TScalarStyle::SingleQuoted => quoted_end(rest, false).map(|n| start + n),
+            TScalarStyle::DoubleQuoted => quoted_end(rest, true).map(|n| start + n),
+            TScalarStyle::Literal | TScalarStyle::Foled => block_end(self.source, start),
+            TScalarStyle::Any => None,
+        };
+        Span {
+            start,
+            end,
+            anchored,
+            shape: Shape::Scalar { style, tagged },
+        }
+    }
+
+    fn collection(&self, frame: Frame, pos: usize) -> Span {
+        let mut children = frame.children;
+        let (start, end) = if frame.flow {
+            (frame.start, Some(pos + 1))
+        } else if frame.map {
+            // The marker of a block mapping points past its first key.
+            let start = children.first().map_or(frame.start, |v| v.start);
+            (start, children.last().and_then(|v| v.end))
+        } else {
+            (frame.start, children.last().and_then(|v| v.end))
+        };
+
+        if !frame.map {
+            return Span {
+                start,
+                end,
+                anchored: frame.anchored,
+                shape: Shape::Seq {
+                    flow: frame.flow,
+                    items: children,
+                },
+            };
+        }
+
+        let mut entries = Vec::new();
+        while children.len() >= 2 {
+            let value = children.remove(1);
+            let key = children.remove(0);
+            entries.push((key, value));
+        }
+        for (key, value) in entries.iter_mut() {
+            if let (Shape::Empty, Some(end)) = (&value.shape, key.end) {
+                let colon = self.source[end..].find(':').map(|n| end + n + 1);
+                value.start = colon.unwrap_or(end);
+                value.end = colon;
+            }
+        }
+        let end = if frame.flow {
+            end
+        } else {
+            entries.last().and_then(|(_, v)| v.end)
+        };
+        Span {
+            start,
+            end,
+            anchored: frame.anchored,
+            shape: Shape::Map {
+                flow: frame.flow,
+                entries,
+            },
+        }
+    }
+}
+
+// quoted_end returns the length of the quoted scalar at the start of `s`.
+fn quoted_end(s: &str, double: bool) -> Option<usize> {
+    let quote = if double { '"' } else { '\'' };
+    let mut chars = s.char_indices().skip(1).peekable();
+    while let Some((i, c)) = chars.next() {
+        if double && c == '\\' {
+            chars.next();
+        } else if c == quote {
+            if !double && chars.peek().map(|(_, c)| *c) == Some('\'') {
+                chars.next();
+                continue;
+            }
+            return Some(i + 1);
+        }
+    }
+    None
+}
+
+// block_end returns the end of a literal or folded scalar whose content starts
+// at `start`, which is the end of its last line indented at least as far as
+// its first.
+fn block_end(source: &str, start: usize) -> Option<usize> {
+    let line = line_start(source, start);
+    let indent = start - line;
+    let mut end = None;
+    let mut pos = line;
+    for l in source[line..].split_inclusive('\n') {
+        let text = l.trim_end_matches(&['\r', '\n'][..]);
+        if !text.trim().is_empty() {
+            if text.len() - text.trim_start_matches(' ').len() < indent {
+                break;
+            }
+            end = Some(pos + text.len());
+        }
+        pos += l.len();
+    }
+    end
+}
+
+fn line_start(source: &str, pos: usize) -> usize {
+    source[..pos].rfind('\n').map_or(0, |n| n + 1)
+}
+
+// line_after returns the start of the line following the one `pos` is on.
+fn line_after(source: &str, pos: usize) -> usize {
+    source[pos..]
+        .find('\n')
+        .map_or(source.len(), |n| pos + n + 1)
+}
+
+// line_first reports whether only indentation precedes `pos` on its line.
+fn line_first(source: &str, pos: usize) -> bool {
+    source[line_start(source, pos)..pos]
+        .chars()
+        .all(|c| c == ' ')
+}
+
+// -
+// Patching
+// -
+
+struct Patch<'a> {
+    source: &'a str,
+    // Replacements of the byte ranges start..end, which are empty for
+    // insertions.
+    edits: Vec<(usize, usize, String)>,
+    // Set when a change cannot be patched locally without breaking the rest of
+    // the document, such as changing an anchored value.
+    failed: bool,
+}
+
+impl<'a> Patch<'a> {
+    // diff patches the text at `span` from `old` to `new`. Returns false if the
+    // value must be replaced as a whole by the caller instead.
+    fn diff(&mut self, span: &Span, old: &Value, new: &Value) -> bool {
+        if old == new {
+            return true;
+        }
+        if span.anchored {
+            self.failed = true;
+            return false;
+        }
+        let mark = self.edits.len();
+        let done = match (&span.shape, old, new) {
+            (Shape::Scalar { style, tagged }, _, _) if !tagged => self.scalar(span, *style, new),
+            (Shape::Empty, _, _) => self.scalar(span, TScalarStyle::Plain, new),
+            (
+                Shape::Map {
+                    flow: false,
+                    entries,
+                },
+                Value::Mapping(old),
+                Value::Mapping(new),
+            ) => self.mapping(span, entries, old, new),
+            (Shape::Seq { flow: false, items }, Value::Sequence(old), Value::Sequence(new)) => {
+                self.sequence(span, items, old, new)
+            }
+            _ => false,
+        };
+        if !done {
+            self.edits.truncate(mark);
+        }
+        done
+    }
+
+    fn scalar(&mut self, span: &Span, style: TScalarStyle, new: &Value) -> bool {
+        let end = match span.end {
+            Some(v) => v,
+            None => return false,
+        };
+        let text = match (style, new) {
+            (_, Value::Mapping(_)) | (_, Value::Sequence(_)) => return false,
+            (TScalarStyle::Literal, _) | (TScalarStyle::Foled, _) => return false,
+            (_, Value::String(s)) if s.contains('\n') => return false,
+            (TScalarStyle::SingleQuoted, Value::String(s)) => {
+                format!("'{}'", s.replace('\'', "''"))
+            }
+            (TScalarStyle::DoubleQuoted, Value::String(s)) => double_quoted(s),
+            _ => match inline(new) {
+                Some(v) if !v.contains('\n') => v,
+                _ => return false,
+            },
+        };
+        let text = match span.shape {
+            Shape::Empty => format!(" {}", text),
+            _ => text,
+        };
+        self.edits.push((span.start, end, text));
+        true
+    }
+
+    fn mapping(
+        &mut self,
+        span: &Span,
+        entries: &[(Span, Span)],
+        old: &serde_yaml::Mapping,
+        new: &serde_yaml::Mapping,
+    ) -> bool {
+        // The decoded mapping keeps the order of the source text, so entries
+        // line up with the spans recorded for them unless a key is repeated,
+        // in which case only the last one is decoded.
+        if entries.len() != old.len() {
+            return false;
+        }
+        let indent = span.start - line_start(self.source, span.start);
+        let mut kept = 0;
+        for (i, ((k, v), (ks, vs))) in old.iter().zip(entries).enumerate() {
+            let end = match vs.end {
+                Some(v) => v,
+                None => return false,
+            };
+            match new.get(k) {
+                Some(nv) => {
+                    kept += 1;
+                    if !self.diff(vs, v, nv) {
+                        if self.failed {
+                            return false;
+                        }
+                        let text = match entry(k, nv, indent) {
+                            Some(v) => v,
+                            None => return false,
+                        };
+                        self.edits.push((ks.start, end, text));
+                    }
+                }
+                None => {
+                    let range = if line_first(self.source, ks.start) {
+                        (
+                            line_start(self.source, ks.start),
+                            line_after(self.source, end),
+                        )
+                    } else if let Some((next, _)) = entries.get(i + 1) {
+                        (ks.start, next.start)
+                    } else {
+                        return false;
+                    };
+                    self.edits.push((range.0, range.1, String::new()));
+                }
+            }
+        }
+
+        let added: Vec<_> = new.iter().filter(|(k, _)| !old.contains_key(k)).collect();
+        if kept == 0 && added.is_empty() {
+            return false;
+        }
+        if added.is_empty() {
+            return true;
+        }
+        let last = match entries.last().and_then(|(_, v)| v.end) {
+            Some(v) => v,
+            None => return false,
+        };
+        let mut text = String::new();
+        for (k, v) in added {
+            match entry(k, v, indent) {
+                Some(v) => text.push_str(&format!("{}{}\n", " ".repeat(indent), v)),
+                None => return false,
+            }
+        }
+        self.insert(line_after(self.source, last), text);
+        true
+    }
+
+    fn sequence(&mut self, span: &Span, items: &[Span], old: &[Value], new: &[Value]) -> bool {
+        if new.is_empty() {
+            return false;
+        }
+        let indent = span.start - line_start(self.source, span.start);
+        for (i, item) in items.iter().enumerate().take(old.len()) {
+            let end = match item.end {
+                Some(v) => v,
+                None => return false,
+            };
+            match new.get(i) {
+                Some(nv) => {
+                    if !self.diff(item, &old[i], nv) {
+                        if self.failed {
+                            return false;
+                        }
+                        let text = match inline(nv) {
+                            Some(v) => indent_lines(&v, indent + 2),
+                            None => return false,
+                        };
+                        self.edits.push((item.start, end, text));
+                    }
+                }
+                None => {
+                    // Only items on lines of their own, as in `- value`, can
+                    // be removed by deleting lines.
+                    let dash = self.source[..item.start].trim_end_matches(' ');
+                    if !dash.ends_with('-') || !line_first(self.source, dash.len() - 1) {
+                        return false;
+                    }
+                    self.edits.push((
+                        line_start(self.source, item.start),
+                        line_after(self.source, end),
+                        String::new(),
+                    ));
+                }
+            }
+        }
+
+        if new.len() <= old.len() {
+            return true;
+        }
+        let last = match items.last().and_then(|v| v.end) {
+            Some(v) => v,
+            None => return false,
+        };
+        let mut text = String::new();
+        for v in &new[old.len()..] {
+            match inline(v) {
+                Some(v) => text.push_str(&format!(
+                    "{}- {}\n",
+                    " ".repeat(indent),
+                    indent_lines(&v, indent + 2)
+                )),
+                None => return false,
+            }
+        }
+        self.insert(line_after(self.source, last), text);
+        true
+    }
+
+    fn insert(&mut self, pos: usize, text: String) {
+        let text = if pos == self.source.len() && !self.source.ends_with('\n') {
+            format!("\n{}", text)
+        } else {
+            text
+        };
+        self.edits.push((pos, pos, text));
+    }
+}
+
+// inline renders a value without the document marker or trailing newline.
+fn inline(v: &Value) -> Option<String> {
+    let s = serde_yaml::to_string(v).ok()?;
+    let s = s.strip_prefix("---\n").unwrap_or(&s);
+    Some(s.trim_end_matches('\n').to_string())
+}
+
+// entry renders a single mapping entry whose key is at column `indent`.
+fn entry(k: &Value, v: &Value, indent: usize) -> Option<String> {
+    let mut m = serde_yaml::Mapping::new();
+    m.insert(k.clone(), v.clone());
+    inline(&Value::Mapping(m)).map(|s| indent_lines(&s, indent))
+}
+
+// indent_lines indents every line but the first, which continues a line that
+// is already indented.
+fn indent_lines(s: &str, indent: usize) -> String {
+    let pad = format!("\n{}", " ".repeat(indent));
+    s.replace('\n', &pad)
+}
+
+fn double_quoted(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,234 @@
+use serde_yaml::Value;
+
+use crate::path::{self, Segment};
+
+// validate checks `v` against a schema, itself a YAML object in the style of
+// JSON Schema, and returns a description of every violation prefixed with the
+// path to the offending value. The supported keywords are:
+//
+// - type: one of object, array, string, integer, number, boolean or null, or
+//   a list of them
+// - required: a list of keys an object must have
+// - properties: an object mapping keys to the schemas their values must match
+// - additionalProperties: false to reject keys not listed in properties
+// - items: the schema every element of an array must match
+// - minimum and maximum: the inclusive range of a number
+// - minLength and maxLength: the range of a string's length in characters
+// - minItems and maxItems: the range of an array's length
+// - enum: a list of the values allowed
+//
+// As in JSON Schema, keywords only apply to values of the type they describe,
+// so `minimum` is ignored for strings. Returns an error if the schema itself is
+// malformed, such as when it contains an unknown keyword.
+pub fn validate(v: &Value, schema: &Value) -> Result<Vec<String>, String> {
+    let mut errors = Vec::new();
+    check(v, schema, &mut Vec::new(), &mut errors)?;
+    Ok(errors)
+}
+
+fn check(
+    v: &Value,
+    schema: &Value,
+    at: &mut Vec<Segment>,
+    errors: &mut Vec<String>,
+) -> Result<(), String> {
+    let schema = match schema {
+        Value::Mapping(m) => m,
+        _ => return Err(format!("{}: schema must be an object", name(at))),
+    };
+    let properties = schema.get(&Value::String("properties".to_string()));
+
+    for (keyword, rule) in schema {
+        let keyword = keyword.as_str().unwrap_or_default();
+        let here = name(at);
+        let malformed = || format!("{}: malformed `{}` in schema", here, keyword);
+        match keyword {
+            "type" => {
+                let types = match rule {
+                    Value::String(t) => vec![t.as_str()],
+                    Value::Sequence(s) => s
+                        .iter()
+                        .map(Value::as_str)
+                        .collect::<Option<_>>()
+                        .ok_or_else(malformed)?,
+                    _ => return Err(malformed()),
+                };
+                if types.iter().any(|t| !TYPES.contains(t)) {
+                    return Err(malformed());
+                }
+                if !types.iter().any(|t| is_type(v, t)) {
+                    errors.push(format!(
+                        "{}: expected {}, got {}",
+                        name(at),
+                        types.join(" or "),
+                        type_name(v)
+                    ));
+                }
+            }
+            "required" => {
+                let keys = rule.as_sequence().ok_or_else(malformed)?;
+                for k in keys {
+                    let k = k.as_str().ok_or_else(malformed)?;
+                    if let Value::Mapping(m) = v {
+                        if !m.contains_key(&Value::String(k.to_string())) {
+                            at.push(Segment::Key(k.to_string()));
+                            errors.push(format!("{}: missing required key", name(at)));
+                            at.pop();
+                        }
+                    }
+                }
+            }
+            "properties" => {
+                let rule = rule.as_mapping().ok_or_else(malformed)?;
+                for (k, schema) in rule {
+                    let k = k.as_str().ok_or_else(malformed)?;
+                    at.push(Segment::Key(k.to_string()));
+                    match v.get(k) {
+                        Some(v) => check(v, schema, at, errors)?,
+                        // Missing keys are only an error if they are required,
+                        // but the schema for them must still be valid.
+                        None => check(&Value::Null, schema, at, &mut Vec::new())?,
+                    }
+                    at.pop();
+                }
+            }
+            "additionalProperties" => {
+                let allowed = rule.as_bool().ok_or_else(malformed)?;
+                if let (false, Value::Mapping(m)) = (allowed, v) {
+                    for (k, _) in m {
+                        let known =
+                            matches!(properties, Some(Value::Mapping(p)) if p.contains_key(k));
+                        if !known {
+                            at.push(Segment::Key(key_name(k)));
+                            errors.push(format!("{}: unexpected key", name(at)));
+                            at.pop();
+                        }
+                    }
+                }
+            }
+            "items" => match v {
+                Value::Sequence(s) => {
+                    for (i, v) in s.iter().enumerate() {
+                        at.push(Segment::Index(i));
+                        check(v, rule, at, errors)?;
+                        at.pop();
+                    }
+                }
+                _ => check(&Value::Null, rule, at, &mut Vec::new())?,
+            },
+            "minimum" | "maximum" => {
+                let limit = rule.as_f64().ok_or_else(malformed)?;
+                if let Some(n) = v.as_f64() {
+                    if keyword == "minimum" && n < limit {
+                        errors.push(format!("{}: {} is less than {}", name(at), n, limit));
+                    }
+                    if keyword == "maximum" && n > limit {
+                        errors.push(format!("{}: {} is greater than {}", name(at), n, limit));
+                    }
+                }
+            }
+            "minLength" | "maxLength" | "minItems" | "maxItems" => {
+                let limit = rule.as_u64().ok_or_else(malformed)? as usize;
+                let len = match (keyword, v) {
+                    ("minLength", Value::String(s)) | ("maxLength", Value::String(s)) => {
+                        s.chars().count()
+                    }
+                    ("minItems", Value::Sequence(s)) | ("maxItems", Value::Sequence(s)) => s.len(),
+                    _ => continue,
+                };
+                if keyword.starts_with("min") && len < limit {
+                    errors.push(format!(
+                        "{}: length {} is less than {}",
+                        name(at),
+                        len,
+                        limit
+                    ));
+                }
+                if keyword.starts_with("max") && len > limit {
+                    errors.push(format!(
+                        "{}: length {} is greater than {}",
+                        name(at),
+                        len,
+                        limit
+                    ));
+                }
+            }
+            "enum" => {
+                let allowed = rule.as_sequence().ok_or_else(malformed)?;
+                if !allowed.contains(v) {
+                    let allowed: Vec<_> = allowed.iter().map(describe).collect();
+                    errors.push(format!(
+                        "{}: {} is not one of {}",
+                        name(at),
+                        describe(v),
+                        allowed.join(", ")
+                    ));
+                }
+            }
+            _ => {
+                return Err(format!(
+                    "{}: unknown schema keyword `{}`",
+                    name(at),
+                    keyword
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+const TYPES: &[&str] = &[
+    "object", "array", "string", "integer", "number", "boolean", "null",
+];
+
+fn is_type(v: &Value, t: &str) -> bool {
+    match t {
+        "object" => v.is_mapping(),
+        "array" => v.is_sequence(),
+        "string" => v.is_string(),
+        "integer" => v.is_i64() || v.is_u64(),
+        "number" => v.is_number(),
+        "boolean" => v.is_bool(),
+        "null" => v.is_null(),
+        _ => false,
+    }
+}
+
+fn type_name(v: &Value) -> &'static str {
+    match v {
+        Value::Mapping(_) => "object",
+        Value::Sequence(_) => "array",
+        Value::String(_) => "string",
+        Value::Number(n) if n.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::Bool(_) => "boolean",
+        Value::Null => "null",
+    }
+}
+
+// name returns the path to a value for use in messages.
+fn name(at: &[Segment]) -> String {
+    if at.is_empty() {
+        return "(root)".to_string();
+    }
+    path::format(at)
+}
+
+fn key_name(k: &Value) -> String {
+    match k {
+        Value::String(s) => s.clone(),
+        v => describe(v),
+    }
+}
+
+// describe renders a scalar the way it would appear in a document.
+fn describe(v: &Value) -> String {
+    serde_yaml::to_string(v)
+        .map(|s| {
+            s.trim_start_matches("---")
+                .trim_start_matches(['\n', ' '].as_ref())
+                .trim_end()
+                .to_string()
+        })
+        .unwrap_or_default()
+}
@@ -0,0 +1,1415 @@
+use log::{debug, error, warn};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::error::{ParseError, Result, YamlError};
+use crate::legacy;
+use crate::merge;
+use crate::node::Node;
+use crate::pack::{self, Cell};
+use crate::path::{self, Segment};
+use crate::pool::{GarbageCollectedPool, Handle};
+use crate::roundtrip::Document;
+use crate::schema;
+
+enum_from_primitive! {
+#[derive(Debug, PartialEq, Clone)]
+pub enum YamlNode {
+    Number = 0,
+    Boolean,
+    String,
+    Object,
+    Array,
+    Null,
+}
+}
+
+impl From<&serde_yaml::Value> for YamlNode {
+    fn from(v: &serde_yaml::Value) -> Self {
+        match v {
+            serde_yaml::Value::Null => YamlNode::Null,
+            serde_yaml::Value::Bool(_) => YamlNode::Boolean,
+            serde_yaml::Value::Number(_) => YamlNode::Number,
+            serde_yaml::Value::String(_) => YamlNode::String,
+            serde_yaml::Value::Sequence(_) => YamlNode::Array,
+            serde_yaml::Value::Mapping(_) => YamlNode::Object,
+        }
+    }
+}
+
+// Entry is what a handle refers to. References address a value inside another
+// node's document and read and write through to it, whereas nodes never see
+// changes made through other handles. References record the layout of the
+// arrays along their path, see Node::layout, so that one to an array item
+// becomes invalid rather than pointing at another item once the array is
+// resized.
+#[derive(Clone)]
+enum Entry {
+    Node(Node),
+    Ref {
+        parent: i32,
+        path: Vec<Segment>,
+        layout: u64,
+    },
+}
+
+// Store holds every node allocated by scripts and implements the operations
+// behind each native, independent of the AMX so it can be tested directly.
+pub struct Store {
+    nodes: GarbageCollectedPool<Entry>,
+    scriptfiles: PathBuf,
+    // New nodes are owned by `owner` so they can be freed when the script that
+    // allocated them is unloaded. The plugin sets it before each native call.
+    owner: u32,
+    // The native currently being called, recorded against new nodes for leak
+    // reports.
+    native: &'static str,
+    // When debug is set, operations on stale handles are logged along with the
+    // caller, which the plugin sets to the native and AMX making the call.
+    debug: bool,
+    caller: String,
+    // When loose is set, getters convert between scalar types instead of
+    // failing with YamlError::WrongType.
+    loose: bool,
+    // The child nodes taken from each document, keyed by Node::document_id, so
+    // they can be detached before the document is written to instead of the
+    // whole document being copied. `children` counts them all.
+    sharers: HashMap<usize, Vec<i32>>,
+    children: usize,
+}
+
+impl Store {
+    pub fn new(scriptfiles: impl Into<PathBuf>) -> Self {
+        Store {
+            nodes: GarbageCollectedPool::default(),
+            scriptfiles: scriptfiles.into(),
+            owner: 0,
+            native: "",
+            debug: false,
+            caller: String::new(),
+            loose: false,
+            sharers: HashMap::new(),
+            children: 0,
+        }
+    }
+
+    pub fn set_owner(&mut self, owner: u32) {
+        self.owner = owner;
+    }
+
+    pub fn set_native(&mut self, native: &'static str) {
+        self.native = native;
+    }
+
+    // free_owned frees every node allocated by `owner`, including those with
+    // garbage collection disabled, and returns how many were freed.
+    pub fn free_owned(&mut self, owner: u32) -> usize {
+        self.nodes.collect_owned(owner)
+    }
+
+    pub fn debug(&self) -> bool {
+        self.debug
+    }
+
+    pub fn set_debug(&mut self, debug: bool) {
+        self.debug = debug;
+    }
+
+    pub fn set_caller(&mut self, caller: String) {
+        self.caller = caller;
+    }
+
+    pub fn set_loose(&mut self, loose: bool) {
+        self.loose = loose;
+    }
+
+    // -
+    // Encoding and decoding
+    // -
+
+    // parse decodes `input`, optionally applying YAML 1.1 merge keys. Also
+    // returns whether the input contained any aliases, which are always
+    // expanded into copies of the values they refer to.
+    pub fn parse(
+        &mut self,
+        input: &str,
+        merge_keys: bool,
+    ) -> std::result::Result<(i32, bool), ParseError> {
+        let mut v: serde_yaml::Value = serde_yaml::from_str(input).map_err(|e| {
+            error!("{}", e);
+            ParseError::from(e)
+        })?;
+        if merge_keys {
+            merge::resolve(&mut v).map_err(|message| {
+                error!("{}", message);
+                ParseError::new(YamlError::Parse, message)
+            })?;
+        }
+        Ok((self.alloc(v)?, merge::has_aliases(input)))
+    }
+
+    pub fn stringify(&mut self, node: i32) -> Result<String> {
+        self.check(node)?;
+        if let Some(Entry::Node(n)) = self.nodes.peek(node) {
+            if let Some(doc) = n.document() {
+                return match doc.render(n.get()) {
+                    Some(v) => Ok(v),
+                    None => {
+                        debug!("node {} could not be patched, encoding it in full", node);
+                        encode(n.get())
+                    }
+                };
+            }
+        }
+        let v = self.value(node)?;
+        encode(v)
+    }
+
+    // parse_round_trip decodes `input` like parse but keeps the text, so that
+    // stringifying the node later only changes the parts of the text that were
+    // modified and leaves comments, key order, anchors and quoting intact.
+    pub fn parse_round_trip(&mut self, input: &str) -> std::result::Result<i32, ParseError> {
+        let v: serde_yaml::Value = serde_yaml::from_str(input).map_err(|e| {
+            error!("{}", e);
+            ParseError::from(e)
+        })?;
+        Ok(self.alloc_document(input, v)?)
+    }
+
+    // parse_all decodes every document in a multi-document stream, allocating
+    // at most `max` of them. Returns the allocated nodes and the total number
+    // of documents in the stream.
+    pub fn parse_all(
+        &mut self,
+        input: &str,
+        max: usize,
+    ) -> std::result::Result<(Vec<i32>, usize), ParseError> {
+        let mut docs = Vec::<serde_yaml::Value>::new();
+        for doc in serde_yaml::Deserializer::from_str(input) {
+            let v = serde_yaml::Value::deserialize(doc).map_err(|e| {
+                error!("{}", e);
+                ParseError::from(e)
+            })?;
+            docs.push(v);
+        }
+
+        let total = docs.len();
+        if total > max {
+            error!(
+                "input contains {} documents but output only holds {}",
+                total, max
+            );
+        }
+
+        let mut nodes = Vec::new();
+        for v in docs.into_iter().take(max) {
+            match self.alloc(v) {
+                Ok(v) => nodes.push(v),
+                Err(e) => {
+                    for node in nodes {
+                        self.nodes.collect_force(node);
+                    }
+                    return Err(e.into());
+                }
+            }
+        }
+        Ok((nodes, total))
+    }
+
+    pub fn stringify_all(&mut self, nodes: &[i32]) -> Result<String> {
+        let mut out = String::new();
+        for node in nodes {
+            // Round-trip nodes are rendered from their own text, which may not
+            // start with a document marker or end with a newline.
+            let doc = self.stringify(*node)?;
+            if !out.is_empty() && !out.ends_with('\n') {
+                out.push('\n');
+            }
+            if !doc.starts_with("---") {
+                out.push_str("---\n");
+            }
+            out.push_str(&doc);
+        }
+        Ok(out)
+    }
+
+    // parse_json decodes JSON into a node. Since JSON is a subset of what YAML
+    // can represent, the node is no different from one decoded from YAML.
+    pub fn parse_json(&mut self, input: &str) -> std::result::Result<i32, ParseError> {
+        let v: serde_yaml::Value = serde_json::from_str(input).map_err(|e| {
+            error!("{}", e);
+            ParseError::from(e)
+        })?;
+        Ok(self.alloc(v)?)
+    }
+
+    // stringify_json encodes a node as JSON. Fails for nodes that have no JSON
+    // equivalent, such as mappings with sequences or mappings as keys.
+    pub fn stringify_json(&mut self, node: i32, pretty: bool) -> Result<String> {
+        let v = self.value(node)?;
+        let s = if pretty {
+            serde_json::to_string_pretty(v)
+        } else {
+            serde_json::to_string(v)
+        };
+        s.map_err(|e| {
+            error!("{}", e);
+            YamlError::Encode
+        })
+    }
+
+    pub fn parse_ini(&mut self, input: &str) -> std::result::Result<i32, ParseError> {
+        match legacy::from_ini(input) {
+            Ok(v) => Ok(self.alloc(v)?),
+            Err(e) => {
+                error!("{}", e.message);
+                Err(e)
+            }
+        }
+    }
+
+    pub fn stringify_ini(&mut self, node: i32) -> Result<String> {
+        let v = self.value(node)?;
+        legacy::to_ini(v).map_err(|e| {
+            error!("{}", e);
+            YamlError::Encode
+        })
+    }
+
+    pub fn parse_toml(&mut self, input: &str) -> std::result::Result<i32, ParseError> {
+        match legacy::from_toml(input) {
+            Ok(v) => Ok(self.alloc(v)?),
+            Err(e) => {
+                error!("{}", e.message);
+                Err(e)
+            }
+        }
+    }
+
+    pub fn stringify_toml(&mut self, node: i32) -> Result<String> {
+        let v = self.value(node)?;
+        legacy::to_toml(v).map_err(|e| {
+            error!("{}", e);
+            YamlError::Encode
+        })
+    }
+
+    pub fn parse_file(&mut self, path: &str) -> std::result::Result<i32, ParseError> {
+        let (_, v) = self.read_file(path)?;
+        Ok(self.alloc(v)?)
+    }
+
+    pub fn parse_file_round_trip(&mut self, path: &str) -> std::result::Result<i32, ParseError> {
+        let (input, v) = self.read_file(path)?;
+        Ok(self.alloc_document(&input, v)?)
+    }
+
+    // read_file reads and decodes a file in scriptfiles, returning its text
+    // along with the value.
+    fn read_file(
+        &self,
+        path: &str,
+    ) -> std::result::Result<(String, serde_yaml::Value), ParseError> {
+        let path = match self.scriptfiles_path(path) {
+            Some(v) => v,
+            None => {
+                let message = format!("path must be relative to scriptfiles: {}", path);
+                error!("{}", message);
+                return Err(ParseError::new(YamlError::File, message));
+            }
+        };
+        let input = match fs::read_to_string(&path) {
+            Ok(v) => v,
+            Err(e) => {
+                let message = format!("{}: {}", path.display(), e);
+                error!("{}", message);
+                return Err(ParseError::new(YamlError::File, message));
+            }
+        };
+        let v: serde_yaml::Value = serde_yaml::from_str(&input).map_err(|e| {
+            error!("{}: {}", path.display(), e);
+            ParseError::from(e)
+        })?;
+        Ok((input, v))
+    }
+
+    pub fn save_file(&mut self, node: i32, path: &str) -> Result<()> {
+        let s = self.stringify(node)?;
+        let path = match self.scriptfiles_path(path) {
+            Some(v) => v,
+            None => {
+                error!("path must be relative to scriptfiles: {}", path);
+                return Err(YamlError::File);
+            }
+        };
+        fs::write(&path, s).map_err(|e| {
+            error!("{}: {}", path.display(), e);
+            YamlError::File
+        })
+    }
+
+    // scriptfiles_path resolves a script-supplied path inside the scriptfiles
+    // directory, rejecting absolute paths and any `..` components so scripts
+    // cannot read or write outside of it.
+    fn scriptfiles_path(&self, path: &str) -> Option<PathBuf> {
+        let path = Path::new(path);
+        let sandboxed = path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+        if !sandboxed {
+            return None;
+        }
+        Some(self.scriptfiles.join(path))
+    }
+
+    // -
+    // Diagnostics
+    // -
+
+    pub fn stats(&self) -> Stats {
+        let mut stats = Stats::default();
+        let null = serde_yaml::Value::Null;
+        for (node, item) in self.nodes.iter() {
+            stats.live += 1;
+            if !item.gc {
+                stats.gc_disabled += 1;
+            }
+            stats.bytes += approx_size(self.resolve(node).unwrap_or(&null));
+        }
+        stats
+    }
+
+    // dump_live_nodes writes a line for each live node to a file in scriptfiles
+    // with its handle, type, garbage collection flag, the native that allocated
+    // it and a truncated preview of its contents.
+    pub fn dump_live_nodes(&mut self, path: &str) -> Result<()> {
+        let path = match self.scriptfiles_path(path) {
+            Some(v) => v,
+            None => {
+                error!("path must be relative to scriptfiles: {}", path);
+                return Err(YamlError::File);
+            }
+        };
+        fs::write(&path, self.live_nodes()).map_err(|e| {
+            error!("{}: {}", path.display(), e);
+            YamlError::File
+        })
+    }
+
+    fn live_nodes(&self) -> String {
+        let null = serde_yaml::Value::Null;
+        let mut out = String::new();
+        for (node, item) in self.nodes.iter() {
+            let v = self.resolve(node).unwrap_or(&null);
+            out.push_str(&format!(
+                "{}\t{:?}\tgc={}\t{}\t{}\n",
+                node,
+                YamlNode::from(v),
+                item.gc,
+                item.origin,
+                preview(v)
+            ));
+        }
+        out
+    }
+
+    // -
+    // Building nodes
+    // -
+
+    pub fn node_type(&mut self, node: i32) -> YamlNode {
+        let null = serde_yaml::Value::Null;
+        let v = self.resolve(node).unwrap_or(&null);
+        debug!("{:?}", v);
+        YamlNode::from(v)
+    }
+
+    // alloc_document allocates a round-trip node for a value decoded from
+    // `input`, or a plain node if the layout of `input` cannot be recorded.
+    fn alloc_document(&mut self, input: &str, v: serde_yaml::Value) -> Result<i32> {
+        match Document::parse(input, v.clone()) {
+            Some(doc) => self.insert(Entry::Node(Node::with_document(v, doc))),
+            None => {
+                debug!("could not record the layout of the input, it will be encoded in full");
+                self.alloc(v)
+            }
+        }
+    }
+
+    pub fn alloc(&mut self, v: serde_yaml::Value) -> Result<i32> {
+        self.insert(Entry::Node(Node::new(v)))
+    }
+
+    // insert allocates a handle for `entry`, failing with YamlError::PoolFull
+    // when the most nodes that can be live at once already are.
+    fn insert(&mut self, entry: Entry) -> Result<i32> {
+        match self.nodes.alloc(entry, self.owner, self.native) {
+            Some(v) => Ok(v),
+            None => {
+                error!(
+                    "cannot allocate more than {} live nodes, see YAML_DumpLiveNodes for leaks",
+                    self.nodes.capacity()
+                );
+                Err(YamlError::PoolFull)
+            }
+        }
+    }
+
+    pub fn object(&mut self, pairs: Vec<(String, i32)>) -> Result<i32> {
+        let mut v = serde_yaml::Value::Mapping(serde_yaml::Mapping::new());
+        for (key, node) in pairs {
+            let node = match self.take(node) {
+                Ok(v) => v,
+                Err(e) => {
+                    error!("invalid YAML node ID passed to YAML_Object");
+                    return Err(e);
+                }
+            };
+            v[key] = node;
+        }
+        self.alloc(v)
+    }
+
+    pub fn array(&mut self, nodes: Vec<i32>) -> Result<i32> {
+        let mut arr = Vec::<serde_yaml::Value>::new();
+        for node in nodes {
+            let node = match self.take(node) {
+                Ok(v) => v,
+                Err(e) => {
+                    error!("invalid YAML node ID passed to YAML_Array");
+                    return Err(e);
+                }
+            };
+            arr.push(node);
+        }
+        self.alloc(serde_yaml::Value::Sequence(arr))
+    }
+
+    pub fn append(&mut self, a: i32, b: i32) -> Result<i32> {
+        let a = self.take(a)?;
+        let b = self.take(b)?;
+
+        match (a, b) {
+            (serde_yaml::Value::Mapping(mut a), serde_yaml::Value::Mapping(b)) => {
+                for (k, v) in b {
+                    a.insert(k, v);
+                }
+                self.alloc(serde_yaml::Value::Mapping(a))
+            }
+            (serde_yaml::Value::Sequence(mut a), serde_yaml::Value::Sequence(b)) => {
+                a.extend(b);
+                self.alloc(serde_yaml::Value::Sequence(a))
+            }
+            _ => {
+                debug!("failed to append: a and b are not both objects or arrays");
+                Err(YamlError::WrongType)
+            }
+        }
+    }
+
+    // -
+    // Objects
+    // -
+
+    pub fn set_object(&mut self, node: i32, key: &str, value: i32) -> Result<()> {
+        let src = self.take(value)?;
+        let dst = self.mapping_mut(node)?;
+        if !src.is_mapping() {
+            return Err(YamlError::WrongType);
+        }
+        dst.insert(serde_yaml::Value::String(key.to_string()), src);
+        Ok(())
+    }
+
+    pub fn set(&mut self, node: i32, key: &str, value: serde_yaml::Value) -> Result<()> {
+        let dst = self.mapping_mut(node)?;
+        dst.insert(serde_yaml::Value::String(key.to_string()), value);
+        Ok(())
+    }
+
+    pub fn has_key(&mut self, node: i32, key: &str) -> bool {
+        self.key(node, key).is_ok()
+    }
+
+    pub fn remove_key(&mut self, node: i32, key: &str) -> Result<()> {
+        let v = self.mapping_mut(node)?;
+        match v.remove(&serde_yaml::Value::String(key.to_string())) {
+            Some(_) => Ok(()),
+            None => Err(YamlError::MissingKey),
+        }
+    }
+
+    pub fn key_type(&mut self, node: i32, key: &str) -> YamlNode {
+        match self.key(node, key) {
+            Ok(v) => YamlNode::from(v),
+            Err(_) => YamlNode::Null,
+        }
+    }
+
+    pub fn get_object(&mut self, node: i32, key: &str) -> Result<i32> {
+        self.key(node, key)?;
+        self.child(node, &[Segment::Key(key.to_string())])
+    }
+
+    pub fn get_object_ref(&mut self, node: i32, key: &str) -> Result<i32> {
+        self.key(node, key)?;
+        self.reference(node, &[Segment::Key(key.to_string())])
+    }
+
+    pub fn get_int(&mut self, node: i32, key: &str) -> Result<i32> {
+        as_int(self.loose, self.key(node, key)?)
+    }
+
+    pub fn get_float(&mut self, node: i32, key: &str) -> Result<f32> {
+        as_float(self.loose, self.key(node, key)?)
+    }
+
+    pub fn get_bool(&mut self, node: i32, key: &str) -> Result<bool> {
+        as_bool(self.loose, self.key(node, key)?)
+    }
+
+    pub fn get_string(&mut self, node: i32, key: &str) -> Result<String> {
+        as_string(self.loose, self.key(node, key)?)
+    }
+
+    pub fn get_int64(&mut self, node: i32, key: &str) -> Result<i64> {
+        as_i64(self.loose, self.key(node, key)?)
+    }
+
+    pub fn get_int_string(&mut self, node: i32, key: &str) -> Result<String> {
+        as_int_string(self.loose, self.key(node, key)?)
+    }
+
+    // set_int_string sets a key to an integer written in decimal, which may be
+    // too large for a cell.
+    pub fn set_int_string(&mut self, node: i32, key: &str, value: &str) -> Result<()> {
+        let value = value.trim();
+        let v: serde_yaml::Value = match (value.parse::<i64>(), value.parse::<u64>()) {
+            (Ok(i), _) => i.into(),
+            (_, Ok(u)) => u.into(),
+            _ => {
+                debug!("{} is not an integer", value);
+                return Err(YamlError::WrongType);
+            }
+        };
+        self.set(node, key, v)
+    }
+
+    // get_int_loose and the like convert between scalar types whether or not
+    // loose conversion is enabled.
+    pub fn get_int_loose(&mut self, node: i32, key: &str) -> Result<i32> {
+        as_int(true, self.key(node, key)?)
+    }
+
+    pub fn get_float_loose(&mut self, node: i32, key: &str) -> Result<f32> {
+        as_float(true, self.key(node, key)?)
+    }
+
+    pub fn get_bool_loose(&mut self, node: i32, key: &str) -> Result<bool> {
+        as_bool(true, self.key(node, key)?)
+    }
+
+    pub fn get_string_loose(&mut self, node: i32, key: &str) -> Result<String> {
+        as_string(true, self.key(node, key)?)
+    }
+
+    // get_int_or and the like return the value of a key, or `default` if the
+    // key is missing or its value is of the wrong type.
+    pub fn get_int_or(&mut self, node: i32, key: &str, default: i32) -> i32 {
+        or_default(self.get_int(node, key), key, default)
+    }
+
+    pub fn get_float_or(&mut self, node: i32, key: &str, default: f32) -> f32 {
+        or_default(self.get_float(node, key), key, default)
+    }
+
+    pub fn get_bool_or(&mut self, node: i32, key: &str, default: bool) -> bool {
+        or_default(self.get_bool(node, key), key, default)
+    }
+
+    // get_string_or also reports whether the value was found rather than the
+    // default used.
+    pub fn get_string_or(&mut self, node: i32, key: &str, default: &str) -> (String, bool) {
+        match self.get_string(node, key) {
+            Ok(v) => (v, true),
+            Err(e) => (or_default(Err(e), key, default.to_string()), false),
+        }
+    }
+
+    pub fn get_array(&mut self, node: i32, key: &str) -> Result<i32> {
+        if !self.key(node, key)?.is_sequence() {
+            return Err(YamlError::WrongType);
+        }
+        self.child(node, &[Segment::Key(key.to_string())])
+    }
+
+    pub fn object_length(&mut self, node: i32) -> Result<usize> {
+        Ok(self.mapping(node)?.len())
+    }
+
+    pub fn object_key(&mut self, node: i32, index: i32) -> Result<String> {
+        let (k, _) = self.entry(node, index)?;
+        match k {
+            serde_yaml::Value::String(k) => Ok(k.clone()),
+            serde_yaml::Value::Number(k) => Ok(k.to_string()),
+            serde_yaml::Value::Bool(k) => Ok(k.to_string()),
+            _ => {
+                debug!("key is not a scalar {:?}", k);
+                Err(YamlError::WrongType)
+            }
+        }
+    }
+
+    pub fn object_value(&mut self, node: i32, index: i32) -> Result<i32> {
+        let (k, v) = self.entry(node, index)?;
+        match k {
+            serde_yaml::Value::String(k) => {
+                let k = k.clone();
+                self.child(node, &[Segment::Key(k)])
+            }
+            // Paths can only address string keys, so values under any other
+            // key are copied instead.
+            _ => {
+                let v = v.clone();
+                self.alloc(v)
+            }
+        }
+    }
+
+    // -
+    // Arrays
+    // -
+
+    pub fn array_length(&mut self, node: i32) -> Result<usize> {
+        Ok(self.sequence(node)?.len())
+    }
+
+    pub fn array_object(&mut self, node: i32, index: i32) -> Result<i32> {
+        self.element(node, index)?;
+        self.child(node, &[Segment::Index(index as usize)])
+    }
+
+    pub fn array_object_ref(&mut self, node: i32, index: i32) -> Result<i32> {
+        self.element(node, index)?;
+        self.reference(node, &[Segment::Index(index as usize)])
+    }
+
+    pub fn array_int(&mut self, node: i32, index: i32) -> Result<i32> {
+        as_int(self.loose, self.element(node, index)?)
+    }
+
+    pub fn array_float(&mut self, node: i32, index: i32) -> Result<f32> {
+        as_float(self.loose, self.element(node, index)?)
+    }
+
+    pub fn array_bool(&mut self, node: i32, index: i32) -> Result<bool> {
+        as_bool(self.loose, self.element(node, index)?)
+    }
+
+    pub fn array_string(&mut self, node: i32, index: i32) -> Result<String> {
+        as_string(self.loose, self.element(node, index)?)
+    }
+
+    // array_to_ints copies up to `max` elements into `output`. On failure,
+    // `output` holds the elements copied before the offending one.
+    pub fn array_to_ints(&mut self, node: i32, max: usize, output: &mut Vec<i32>) -> Result<()> {
+        let loose = self.loose;
+        let v = self.sequence(node)?;
+        for (i, e) in v.iter().take(max).enumerate() {
+            match as_int(loose, e) {
+                Ok(e) => output.push(e),
+                Err(err) => {
+                    debug!("array element {} is not a 32-bit integer {:?}", i, e);
+                    return Err(err);
+                }
+            }
+        }
+        if v.len() > max {
+            return Err(YamlError::OutOfRange);
+        }
+        Ok(())
+    }
+
+    // array_to_floats copies up to `max` elements into `output`. On failure,
+    // `output` holds the elements copied before the offending one.
+    pub fn array_to_floats(&mut self, node: i32, max: usize, output: &mut Vec<f32>) -> Result<()> {
+        let loose = self.loose;
+        let v = self.sequence(node)?;
+        for (i, e) in v.iter().take(max).enumerate() {
+            match as_float(loose, e) {
+                Ok(e) => output.push(e),
+                Err(err) => {
+                    debug!("array element {} is not a float {:?}", i, e);
+                    return Err(err);
+                }
+            }
+        }
+        if v.len() > max {
+            return Err(YamlError::OutOfRange);
+        }
+        Ok(())
+    }
+
+    // array_push, array_insert and array_set only consume `value` once the
+    // write is known to succeed, so a failed call leaves it intact.
+    pub fn array_push(&mut self, node: i32, value: i32) -> Result<()> {
+        self.sequence(node)?;
+        let src = self.take(value)?;
+        self.array_push_value(node, src)
+    }
+
+    pub fn array_push_value(&mut self, node: i32, value: serde_yaml::Value) -> Result<()> {
+        self.sequence_mut(node)?.push(value);
+        Ok(())
+    }
+
+    pub fn array_insert(&mut self, node: i32, index: i32, value: i32) -> Result<()> {
+        if index < 0 || index as usize > self.sequence(node)?.len() {
+            return Err(YamlError::OutOfRange);
+        }
+        let src = self.take(value)?;
+        self.sequence_mut(node)?.insert(index as usize, src);
+        self.resized(node)
+    }
+
+    pub fn array_remove(&mut self, node: i32, index: i32) -> Result<()> {
+        let v = self.sequence_mut(node)?;
+        if index < 0 || index as usize >= v.len() {
+            return Err(YamlError::OutOfRange);
+        }
+        v.remove(index as usize);
+        self.resized(node)
+    }
+
+    pub fn array_set(&mut self, node: i32, index: i32, value: i32) -> Result<()> {
+        self.element(node, index)?;
+        let src = self.take(value)?;
+        self.sequence_mut(node)?[index as usize] = src;
+        Ok(())
+    }
+
+    // -
+    // Paths
+    // -
+
+    pub fn get_path_int(&mut self, node: i32, path: &str) -> Result<i32> {
+        as_int(self.loose, self.path(node, path)?)
+    }
+
+    pub fn get_path_float(&mut self, node: i32, path: &str) -> Result<f32> {
+        as_float(self.loose, self.path(node, path)?)
+    }
+
+    pub fn get_path_bool(&mut self, node: i32, path: &str) -> Result<bool> {
+        as_bool(self.loose, self.path(node, path)?)
+    }
+
+    pub fn get_path_string(&mut self, node: i32, path: &str) -> Result<String> {
+        as_string(self.loose, self.path(node, path)?)
+    }
+
+    pub fn get_path_node(&mut self, node: i32, path: &str) -> Result<i32> {
+        let segments = parse_path(path)?;
+        self.lookup(node, &segments)?;
+        self.child(node, &segments)
+    }
+
+    pub fn set_path_node(&mut self, node: i32, path: &str, value: i32) -> Result<()> {
+        let segments = parse_path(path)?;
+        let src = self.take(value)?;
+        self.set_segments(node, &segments, src)
+    }
+
+    pub fn set_path(&mut self, node: i32, path: &str, value: serde_yaml::Value) -> Result<()> {
+        let segments = parse_path(path)?;
+        self.set_segments(node, &segments, value)
+    }
+
+    fn set_segments(
+        &mut self,
+        node: i32,
+        segments: &[Segment],
+        value: serde_yaml::Value,
+    ) -> Result<()> {
+        let v = self.value_mut(node)?;
+        let v = path::lookup_or_create(v, segments).map_err(|i| {
+            debug!(
+                "path segment {} conflicts with an existing value",
+                segments[i]
+            );
+            YamlError::PathSegment(i)
+        })?;
+        *v = value;
+        Ok(())
+    }
+
+    // -
+    // Primitive nodes
+    // -
+
+    pub fn get_node_int(&mut self, node: i32) -> Result<i32> {
+        as_int(self.loose, &self.take(node)?)
+    }
+
+    pub fn get_node_float(&mut self, node: i32) -> Result<f32> {
+        as_float(self.loose, &self.take(node)?)
+    }
+
+    pub fn get_node_bool(&mut self, node: i32) -> Result<bool> {
+        as_bool(self.loose, &self.take(node)?)
+    }
+
+    pub fn get_node_string(&mut self, node: i32) -> Result<String> {
+        let v = match self.take(node) {
+            Ok(v) => v,
+            Err(e) => {
+                debug!("value under {} doesn't exist", node);
+                return Err(e);
+            }
+        };
+        match as_string(self.loose, &v) {
+            Ok(v) => Ok(v),
+            Err(e) => {
+                debug!("value is not a string {:?}", v);
+                Err(e)
+            }
+        }
+    }
+
+    // -
+    // Packing
+    // -
+
+    // unpack copies the values named by a format spec into consecutive cells
+    // of `dest`. Every value that can be read is copied even if others can't,
+    // in which case the error for the first of those is returned.
+    pub fn unpack(&mut self, node: i32, format: &str, dest: &mut [i32]) -> Result<()> {
+        let fields = parse_format(format)?;
+        let size: usize = fields.iter().map(|f| f.cell.size()).sum();
+        if size > dest.len() {
+            error!(
+                "format needs {} cells but output only holds {}",
+                size,
+                dest.len()
+            );
+            return Err(YamlError::OutOfRange);
+        }
+
+        let loose = self.loose;
+        let mut result = Ok(());
+        let mut offset = 0;
+        for field in fields {
+            let cells = &mut dest[offset..offset + field.cell.size()];
+            offset += field.cell.size();
+            let written = self
+                .lookup(node, &field.path)
+                .and_then(|v| match field.cell {
+                    Cell::Int => as_int(loose, v).map(|v| cells[0] = v),
+                    Cell::Float => as_float(loose, v).map(|v| cells[0] = v.to_bits() as i32),
+                    Cell::Bool => as_bool(loose, v).map(|v| cells[0] = v as i32),
+                    Cell::String(_) => as_string(loose, v).map(|v| pack::write_string(cells, &v)),
+                });
+            if let (Ok(_), Err(e)) = (&result, written) {
+                result = Err(e);
+            }
+        }
+        result
+    }
+
+    // pack builds an object from consecutive cells of `src` as described by a
+    // format spec, the reverse of unpack.
+    pub fn pack(&mut self, format: &str, src: &[i32]) -> Result<i32> {
+        let fields = parse_format(format)?;
+        let size: usize = fields.iter().map(|f| f.cell.size()).sum();
+        if size > src.len() {
+            error!(
+                "format needs {} cells but input only holds {}",
+                size,
+                src.len()
+            );
+            return Err(YamlError::OutOfRange);
+        }
+
+        let mut root = serde_yaml::Value::Mapping(serde_yaml::Mapping::new());
+        let mut offset = 0;
+        for field in fields {
+            let cells = &src[offset..offset + field.cell.size()];
+            offset += field.cell.size();
+            let v = path::lookup_or_create(&mut root, &field.path).map_err(|i| {
+                debug!(
+                    "path segment {} conflicts with an existing value",
+                    field.path[i]
+                );
+                YamlError::PathSegment(i)
+            })?;
+            *v = match field.cell {
+                Cell::Int => cells[0].into(),
+                Cell::Float => f32::from_bits(cells[0] as u32).into(),
+                Cell::Bool => (cells[0] != 0).into(),
+                Cell::String(_) => pack::read_string(cells),
+            };
+        }
+        self.alloc(root)
+    }
+
+    // -
+    // Validation
+    // -
+
+    // validate checks a node against a schema node, returning every violation
+    // found, or YamlError::Schema if the schema is malformed.
+    pub fn validate(&mut self, node: i32, schema: i32) -> Result<Vec<String>> {
+        let v = self.resolve(node)?;
+        let schema = self.resolve(schema)?;
+        schema::validate(v, schema).map_err(|e| {
+            error!("{}", e);
+            YamlError::Schema
+        })
+    }
+
+    // -
+    // Garbage collection
+    // -
+
+    pub fn toggle_gc(&mut self, node: i32, set: bool) -> Result<()> {
+        self.check(node)?;
+        self.nodes.set_gc(node, set).ok_or(YamlError::InvalidNode)
+    }
+
+    pub fn cleanup(&mut self, node: i32, auto: bool) -> Result<()> {
+        self.check(node)?;
+        let collected = if auto {
+            self.nodes.collect(node)
+        } else {
+            self.nodes.collect_force(node)
+        };
+        match collected {
+            Some(_) => Ok(()),
+            None => Err(YamlError::InvalidNode),
+        }
+    }
+
+    // -
+    // Lookups shared by the operations above
+    // -
+
+    // check fails for handles that do not refer to a live node. In debug mode,
+    // stale handles are logged with the caller so that use-after-free bugs in
+    // scripts can be traced back to the native that touched them.
+    fn check(&self, node: i32) -> Result<()> {
+        match self.nodes.handle(node) {
+            Handle::Live => Ok(()),
+            Handle::Stale { index, generation } => {
+                if self.debug {
+                    error!(
+                        "{} used node {} after it was freed, slot {} is now at generation {}",
+                        self.caller, node, index, generation
+                    );
+                }
+                Err(YamlError::InvalidNode)
+            }
+            Handle::Invalid => Err(YamlError::InvalidNode),
+        }
+    }
+
+    // target returns the node holding the value behind a handle and the path to
+    // the value within it, which is empty unless the handle is a reference.
+    fn target(&self, node: i32) -> Result<(i32, Vec<Segment>)> {
+        self.check(node)?;
+        match self.nodes.peek(node) {
+            Some(Entry::Node(_)) => Ok((node, Vec::new())),
+            Some(Entry::Ref {
+                parent,
+                path,
+                layout,
+            }) => {
+                if self.check(*parent).is_err() {
+                    debug!("reference {} outlived its parent {}", node, parent);
+                    return Err(YamlError::InvalidNode);
+                }
+                match self.nodes.peek(*parent) {
+                    Some(Entry::Node(n)) if n.layout(path) == *layout => (),
+                    _ => {
+                        debug!("reference {} points into an array that was resized", node);
+                        return Err(YamlError::InvalidNode);
+                    }
+                }
+                Ok((*parent, path.clone()))
+            }
+            None => Err(YamlError::InvalidNode),
+        }
+    }
+
+    fn resolve(&self, node: i32) -> Result<&serde_yaml::Value> {
+        let (target, path) = self.target(node)?;
+        let v = match self.nodes.peek(target) {
+            Some(Entry::Node(n)) => n.get(),
+            _ => return Err(YamlError::InvalidNode),
+        };
+        path::lookup(v, &path).map_err(|_| {
+            debug!("reference {} no longer points to a value", node);
+            YamlError::InvalidNode
+        })
+    }
+
+    fn value(&mut self, node: i32) -> Result<&serde_yaml::Value> {
+        self.resolve(node)
+    }
+
+    fn value_mut(&mut self, node: i32) -> Result<&mut serde_yaml::Value> {
+        let (target, path) = self.target(node)?;
+        self.unshare(target);
+        let v = match self.nodes.get(target) {
+            Some(Entry::Node(n)) => n.get_mut(),
+            _ => return Err(YamlError::InvalidNode),
+        };
+        path::lookup_mut(v, &path).map_err(|_| {
+            debug!("reference {} no longer points to a value", node);
+            YamlError::InvalidNode
+        })
+    }
+
+    fn take(&mut self, node: i32) -> Result<serde_yaml::Value> {
+        let (target, _) = self.target(node)?;
+        if target != node {
+            // Consuming a reference copies the value it points to and leaves
+            // the parent document untouched.
+            let v = self.resolve(node)?.clone();
+            self.nodes.take(node);
+            return Ok(v);
+        }
+        self.unshare(node);
+        match self.nodes.take(node) {
+            Some(Entry::Node(n)) => Ok(n.into_value()),
+            _ => Err(YamlError::InvalidNode),
+        }
+    }
+
+    // child allocates a node that shares the document of `node`, addressing the
+    // value at `segments` below it without copying it.
+    fn child(&mut self, node: i32, segments: &[Segment]) -> Result<i32> {
+        let (target, mut path) = self.target(node)?;
+        path.extend_from_slice(segments);
+        let child = match self.nodes.peek(target) {
+            Some(Entry::Node(n)) => n.child(&path),
+            _ => None,
+        };
+        let child = match child {
+            Some(v) => v,
+            None => return Err(YamlError::InvalidNode),
+        };
+        let id = child.document_id();
+        let child = self.insert(Entry::Node(child))?;
+        self.share(id, child);
+        Ok(child)
+    }
+
+    // share records that `child` holds the document identified by `id`.
+    fn share(&mut self, id: usize, child: i32) {
+        self.sharers.entry(id).or_default().push(child);
+        self.children += 1;
+        // Children that were freed or detached some other way are only dropped
+        // from the lists when their document is written to, so the lists are
+        // pruned whenever they grow well past the number of live nodes.
+        if self.children <= 2 * self.nodes.live() + 1024 {
+            return;
+        }
+        let nodes = &self.nodes;
+        self.sharers.retain(|id, children| {
+            children.retain(
+                |c| matches!(nodes.peek(*c), Some(Entry::Node(n)) if n.document_id() == *id),
+            );
+            !children.is_empty()
+        });
+        self.children = self.sharers.values().map(|v| v.len()).sum();
+    }
+
+    // unshare detaches the children still sharing the document of `node`
+    // before it is written to or consumed, so only their subtrees are copied
+    // rather than the whole document.
+    fn unshare(&mut self, node: i32) {
+        let id = match self.nodes.peek(node) {
+            Some(Entry::Node(n)) if n.shared() => n.document_id(),
+            _ => return,
+        };
+        let children = match self.sharers.remove(&id) {
+            Some(v) => v,
+            None => return,
+        };
+        self.children -= children.len();
+        for child in children {
+            if let Some(Entry::Node(n)) = self.nodes.get(child) {
+                if n.document_id() == id {
+                    n.detach();
+                }
+            }
+        }
+    }
+
+    // reference allocates a handle to the value at `segments` below `node` that
+    // reads and writes through to the document of `node`. References to
+    // references point straight at the underlying node.
+    fn reference(&mut self, node: i32, segments: &[Segment]) -> Result<i32> {
+        let (parent, mut path) = self.target(node)?;
+        path.extend_from_slice(segments);
+        let layout = match self.nodes.peek(parent) {
+            Some(Entry::Node(n)) => n.layout(&path),
+            _ => return Err(YamlError::InvalidNode),
+        };
+        self.insert(Entry::Ref {
+            parent,
+            path,
+            layout,
+        })
+    }
+
+    // resized records that the array behind `node` had items inserted or
+    // removed, invalidating references to items in it.
+    fn resized(&mut self, node: i32) -> Result<()> {
+        let (target, path) = self.target(node)?;
+        match self.nodes.get(target) {
+            Some(Entry::Node(n)) => n.resize(&path),
+            _ => return Err(YamlError::InvalidNode),
+        }
+        Ok(())
+    }
+
+    fn mapping(&mut self, node: i32) -> Result<&serde_yaml::Mapping> {
+        self.value(node)?.as_mapping().ok_or(YamlError::WrongType)
+    }
+
+    fn mapping_mut(&mut self, node: i32) -> Result<&mut serde_yaml::Mapping> {
+        self.value_mut(node)?
+            .as_mapping_mut()
+            .ok_or(YamlError::WrongType)
+    }
+
+    fn sequence(&mut self, node: i32) -> Result<&serde_yaml::Sequence> {
+        self.value(node)?.as_sequence().ok_or(YamlError::WrongType)
+    }
+
+    fn sequence_mut(&mut self, node: i32) -> Result<&mut serde_yaml::Sequence> {
+        self.value_mut(node)?
+            .as_sequence_mut()
+            .ok_or(YamlError::WrongType)
+    }
+
+    fn key(&mut self, node: i32, key: &str) -> Result<&serde_yaml::Value> {
+        self.mapping(node)?
+            .get(&serde_yaml::Value::String(key.to_string()))
+            .ok_or(YamlError::MissingKey)
+    }
+
+    fn entry(&mut self, node: i32, index: i32) -> Result<(&serde_yaml::Value, &serde_yaml::Value)> {
+        self.mapping(node)?
+            .iter()
+            .nth(index as usize)
+            .ok_or(YamlError::OutOfRange)
+    }
+
+    fn element(&mut self, node: i32, index: i32) -> Result<&serde_yaml::Value> {
+        self.sequence(node)?
+            .get(index as usize)
+            .ok_or(YamlError::OutOfRange)
+    }
+
+    fn path(&mut self, node: i32, path: &str) -> Result<&serde_yaml::Value> {
+        let segments = parse_path(path)?;
+        self.lookup(node, &segments)
+    }
+
+    fn lookup(&mut self, node: i32, segments: &[Segment]) -> Result<&serde_yaml::Value> {
+        let v = self.value(node)?;
+        path::lookup(v, segments).map_err(|i| {
+            debug!("path segment {} not found", segments[i]);
+            YamlError::PathSegment(i)
+        })
+    }
+}
+
+// Stats summarises the nodes that are currently allocated.
+#[derive(Debug, Default, PartialEq)]
+pub struct Stats {
+    pub live: usize,
+    pub gc_disabled: usize,
+    // An estimate of the memory held by live nodes, in bytes. Documents shared
+    // by several handles are counted once for each of them.
+    pub bytes: usize,
+}
+
+const PREVIEW_LENGTH: usize = 64;
+
+fn approx_size(v: &serde_yaml::Value) -> usize {
+    let size = std::mem::size_of::<serde_yaml::Value>();
+    match v {
+        serde_yaml::Value::String(s) => size + s.len(),
+        serde_yaml::Value::Sequence(s) => size + s.iter().map(approx_size).sum::<usize>(),
+        serde_yaml::Value::Mapping(m) => {
+            size + m
+                .iter()
+                .map(|(k, v)| approx_size(k) + approx_size(v))
+                .sum::<usize>()
+        }
+        _ => size,
+    }
+}
+
+// preview renders a node on a single line, truncated to PREVIEW_LENGTH
+// characters.
+fn preview(v: &serde_yaml::Value) -> String {
+    let s = serde_yaml::to_string(v).unwrap_or_default();
+    let s = s.trim_start_matches("---").trim().replace('\n', " ");
+    match s.char_indices().nth(PREVIEW_LENGTH) {
+        Some((i, _)) => format!("{}...", &s[..i]),
+        None => s,
+    }
+}
+
+fn or_default<T>(result: Result<T>, key: &str, default: T) -> T {
+    match result {
+        Ok(v) => v,
+        Err(e) => {
+            debug!("using default for {}: {:?}", key, e);
+            default
+        }
+    }
+}
+
+fn parse_format(format: &str) -> Result<Vec<pack::Field>> {
+    pack::parse_format(format).ok_or_else(|| {
+        error!("invalid format spec: {}", format);
+        YamlError::Format
+    })
+}
+
+fn parse_path(path: &str) -> Result<Vec<Segment>> {
+    path::parse(path).ok_or_else(|| {
+        error!("invalid path expression: {}", path);
+        YamlError::InvalidPath
+    })
+}
+
+fn encode(v: &serde_yaml::Value) -> Result<String> {
+    serde_yaml::to_string(v).map_err(|e| {
+        error!("{}", e);
+        YamlError::Encode
+    })
+}
+
+// as_int and the like convert a value to the type a getter returns. When
+// `loose` is set, scalars of other types are converted where that can be done
+// without guessing, such as numeric strings to numbers and YAML 1.1 spellings
+// like `yes` and `off` to booleans, logging a warning for each conversion.
+fn as_int(loose: bool, v: &serde_yaml::Value) -> Result<i32> {
+    let i = as_i64(loose, v)?;
+    i32::try_from(i).map_err(|_| {
+        debug!("{} does not fit in 32 bits", i);
+        YamlError::OutOfRange
+    })
+}
+
+// as_i64 fails with YamlError::OutOfRange rather than wrapping for integers
+// too large to represent.
+fn as_i64(loose: bool, v: &serde_yaml::Value) -> Result<i64> {
+    if let serde_yaml::Value::Number(n) = v {
+        if let Some(i) = n.as_i64() {
+            return Ok(i);
+        }
+        if n.is_u64() {
+            debug!("{} does not fit in 64 bits", n);
+            return Err(YamlError::OutOfRange);
+        }
+    }
+    if !loose {
+        return Err(YamlError::WrongType);
+    }
+    let f = match v {
+        serde_yaml::Value::Number(n) => n.as_f64(),
+        serde_yaml::Value::String(s) => match s.trim().parse::<i64>() {
+            Ok(i) => return coerced(v, "an integer", i),
+            Err(_) => s.trim().parse::<f64>().ok(),
+        },
+        serde_yaml::Value::Bool(b) => Some(*b as i64 as f64),
+        _ => None,
+    };
+    match f {
+        Some(f) if f >= i64::MIN as f64 && f < i64::MAX as f64 => {
+            coerced(v, "an integer", f as i64)
+        }
+        Some(f) if f.is_finite() => {
+            debug!("{} does not fit in 64 bits", f);
+            Err(YamlError::OutOfRange)
+        }
+        _ => Err(YamlError::WrongType),
+    }
+}
+
+// as_int_string returns integers of any size in decimal, including those too
+// large for as_i64.
+fn as_int_string(loose: bool, v: &serde_yaml::Value) -> Result<String> {
+    match v {
+        serde_yaml::Value::Number(n) if n.is_u64() => Ok(n.to_string()),
+        _ => as_i64(loose, v).map(|i| i.to_string()),
+    }
+}
+
+fn as_float(loose: bool, v: &serde_yaml::Value) -> Result<f32> {
+    if let Some(f) = v.as_f64() {
+        return Ok(f as f32);
+    }
+    if !loose {
+        return Err(YamlError::WrongType);
+    }
+    match v.as_str().and_then(|s| s.trim().parse::<f32>().ok()) {
+        Some(f) if f.is_finite() => coerced(v, "a float", f),
+        _ => Err(YamlError::WrongType),
+    }
+}
+
+fn as_bool(loose: bool, v: &serde_yaml::Value) -> Result<bool> {
+    if let Some(b) = v.as_bool() {
+        return Ok(b);
+    }
+    if !loose {
+        return Err(YamlError::WrongType);
+    }
+    let b = match v {
+        serde_yaml::Value::String(s) => match s.trim() {
+            "y" | "Y" | "yes" | "Yes" | "YES" | "true" | "True" | "TRUE" | "on" | "On" | "ON"
+            | "1" => Some(true),
+            "n" | "N" | "no" | "No" | "NO" | "false" | "False" | "FALSE" | "off" | "Off"
+            | "OFF" | "0" => Some(false),
+            _ => None,
+        },
+        serde_yaml::Value::Number(n) => match n.as_i64() {
+            Some(1) => Some(true),
+            Some(0) => Some(false),
+            _ => None,
+        },
+        _ => None,
+    };
+    match b {
+        Some(b) => coerced(v, "a boolean", b),
+        None => Err(YamlError::WrongType),
+    }
+}
+
+fn as_string(loose: bool, v: &serde_yaml::Value) -> Result<String> {
+    match v {
+        serde_yaml::Value::String(s) => Ok(s.clone()),
+        serde_yaml::Value::Number(n) if loose => coerced(v, "a string", n.to_string()),
+        serde_yaml::Value::Bool(b) if loose => coerced(v, "a string", b.to_string()),
+        _ => Err(YamlError::WrongType),
+    }
+}
+
+fn coerced<T>(v: &serde_yaml::Value, to: &str, result: T) -> Result<T> {
+    warn!("converted {} to {}", preview(v), to);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,1302 @@
+use super::*;
+use serde::Serialize;
+
+// These mirror the y_testing cases in test.pwn, calling the store directly
+// instead of going through the natives.
+
+fn store() -> Store {
+    Store::new(std::env::temp_dir())
+}
+
+fn val<T: Serialize>(s: &mut Store, v: T) -> i32 {
+    s.alloc(serde_yaml::to_value(v).unwrap()).unwrap()
+}
+
+fn obj(s: &mut Store, pairs: Vec<(&str, i32)>) -> i32 {
+    let pairs = pairs.into_iter().map(|(k, v)| (k.to_string(), v)).collect();
+    s.object(pairs).unwrap()
+}
+
+fn doc(s: &mut Store, input: &str) -> i32 {
+    s.parse(input, false).unwrap().0
+}
+
+fn ints(s: &mut Store, values: &[i32]) -> i32 {
+    let nodes = values.iter().map(|v| val(s, v)).collect();
+    s.array(nodes).unwrap()
+}
+
+#[test]
+fn parse() {
+    let mut s = store();
+    let node = doc(&mut s, "a: 1\nb:\n  - x\n  - y\n");
+    assert_eq!(s.stringify(node).unwrap(), "---\na: 1\nb:\n  - x\n  - y\n");
+}
+
+#[test]
+fn parse_all() {
+    let mut s = store();
+    let (docs, total) = s.parse_all("a: 1\n---\nb: 2\n---\n- 3\n", 4).unwrap();
+    assert_eq!(total, 3);
+    assert_eq!(s.get_int(docs[0], "a"), Ok(1));
+    assert_eq!(s.get_int(docs[1], "b"), Ok(2));
+    assert_eq!(s.node_type(docs[2]), YamlNode::Array);
+
+    let (docs, total) = s.parse_all("a: 1\n---\nb: 2\n", 1).unwrap();
+    assert_eq!(docs.len(), 1);
+    assert_eq!(total, 2);
+}
+
+#[test]
+fn stringify_all() {
+    let mut s = store();
+    let a = val(&mut s, 1);
+    let a = obj(&mut s, vec![("a", a)]);
+    let b = val(&mut s, 2);
+    let b = obj(&mut s, vec![("b", b)]);
+    assert_eq!(s.stringify_all(&[a, b]).unwrap(), "---\na: 1\n---\nb: 2\n");
+    assert_eq!(s.stringify_all(&[a, 999]), Err(YamlError::InvalidNode));
+}
+
+#[test]
+fn parse_error() {
+    let mut s = store();
+    let e = s.parse("key: value\n@invalid", false).unwrap_err();
+    assert_eq!(e.code, YamlError::Parse);
+    assert_eq!(e.line, 2);
+    assert_eq!(e.column, 1);
+    assert!(!e.message.is_empty());
+}
+
+#[test]
+fn parse_merge() {
+    let mut s = store();
+    let input = "defaults: &defaults\n  max_speed: 200\n  mass: 1000\n\
+                 infernus:\n  <<: *defaults\n  mass: 1400\n\
+                 turismo:\n  <<: [{mass: 1200}, *defaults]\n";
+    let (node, aliases) = s.parse(input, true).unwrap();
+    assert!(aliases);
+    assert_eq!(s.get_path_int(node, "infernus.max_speed"), Ok(200));
+    assert_eq!(s.get_path_int(node, "infernus.mass"), Ok(1400));
+    assert_eq!(s.get_path_int(node, "turismo.max_speed"), Ok(200));
+    assert_eq!(s.get_path_int(node, "turismo.mass"), Ok(1200));
+    let infernus = s.get_object(node, "infernus").unwrap();
+    assert!(!s.has_key(infernus, "<<"));
+
+    let (node, aliases) = s.parse(input, false).unwrap();
+    assert!(aliases);
+    assert_eq!(
+        s.get_path_int(node, "infernus.max_speed"),
+        Err(YamlError::PathSegment(1))
+    );
+
+    let (_, aliases) = s.parse("a: '*'\n", true).unwrap();
+    assert!(!aliases);
+    let e = s.parse("a:\n  <<: 5\n", true).unwrap_err();
+    assert_eq!(e.code, YamlError::Parse);
+}
+
+#[test]
+fn json() {
+    let mut s = store();
+    let node = s
+        .parse_json(r#"{"name":"test","limits":{"players":50},"list":[1,2.5,true,null]}"#)
+        .unwrap();
+    assert_eq!(s.get_path_int(node, "limits.players"), Ok(50));
+    assert_eq!(
+        s.stringify(node).unwrap(),
+        "---\nname: test\nlimits:\n  players: 50\nlist:\n  - 1\n  - 2.5\n  - true\n  - ~\n"
+    );
+    assert_eq!(
+        s.stringify_json(node, false).unwrap(),
+        r#"{"name":"test","limits":{"players":50},"list":[1,2.5,true,null]}"#
+    );
+    assert_eq!(
+        s.stringify_json(node, true).unwrap(),
+        "{\n  \"name\": \"test\",\n  \"limits\": {\n    \"players\": 50\n  },\n  \"list\": [\n    1,\n    2.5,\n    true,\n    null\n  ]\n}"
+    );
+
+    let yaml = doc(&mut s, "? [1, 2]\n: value\n");
+    assert_eq!(s.stringify_json(yaml, false), Err(YamlError::Encode));
+
+    let e = s.parse_json("{\n  \"a\": }").unwrap_err();
+    assert_eq!(e.code, YamlError::Parse);
+    assert_eq!(e.line, 2);
+    assert_eq!(e.column, 8);
+}
+
+#[test]
+fn ini() {
+    let mut s = store();
+    let node = s
+        .parse_ini("; player file\nname = Southclaws\nscore=50\n\n[stats]\nkills = 10\nratio = 1.5\nbanned = false\nnote = \"42\"\n")
+        .unwrap();
+    assert_eq!(s.get_string(node, "name"), Ok("Southclaws".to_string()));
+    assert_eq!(s.get_int(node, "score"), Ok(50));
+    assert_eq!(s.get_path_int(node, "stats.kills"), Ok(10));
+    assert_eq!(s.get_path_float(node, "stats.ratio"), Ok(1.5));
+    assert_eq!(s.get_path_bool(node, "stats.banned"), Ok(false));
+    assert_eq!(s.get_path_string(node, "stats.note"), Ok("42".to_string()));
+    assert_eq!(
+        s.stringify_ini(node).unwrap(),
+        "name = Southclaws\nscore = 50\n\n[stats]\nkills = 10\nratio = 1.5\nbanned = false\nnote = \"42\"\n"
+    );
+
+    // Strings that would decode as something else survive a round trip.
+    let strings = doc(
+        &mut s,
+        "id: \"007\"\nflag: \"true\"\npadded: \"  x \"\nquoted: '\"hi\"'\nempty: \"\"\n",
+    );
+    let out = s.stringify_ini(strings).unwrap();
+    let copy = s.parse_ini(&out).unwrap();
+    for key in &["id", "flag", "padded", "quoted", "empty"] {
+        assert_eq!(s.get_string(copy, key), s.get_string(strings, key));
+    }
+
+    let e = s.parse_ini("a = 1\nbroken\n").unwrap_err();
+    assert_eq!(e.code, YamlError::Parse);
+    assert_eq!(e.line, 2);
+
+    // Keys that would be read back differently can't be encoded.
+    let keys = doc(&mut s, "a b: 1\n'7': 2\nstats:\n  k/d: 3\n");
+    let out = s.stringify_ini(keys).unwrap();
+    let copy = s.parse_ini(&out).unwrap();
+    assert_eq!(s.get_int(copy, "a b"), Ok(1));
+    assert_eq!(s.get_int(copy, "7"), Ok(2));
+    assert_eq!(s.get_path_int(copy, "stats.k/d"), Ok(3));
+    for input in &[
+        "a=b: 1\n",
+        "'[a]': 1\n",
+        "'; a': 1\n",
+        "'#a': 1\n",
+        "' a': 1\n",
+        "\"a\\nb\": 1\n",
+        "a]b:\n  c: 1\n",
+    ] {
+        let node = doc(&mut s, input);
+        assert_eq!(s.stringify_ini(node), Err(YamlError::Encode), "{}", input);
+    }
+
+    let nested = doc(&mut s, "a:\n  b:\n    c: 1\n");
+    assert_eq!(s.stringify_ini(nested), Err(YamlError::Encode));
+    let list = ints(&mut s, &[1]);
+    assert_eq!(s.stringify_ini(list), Err(YamlError::Encode));
+}
+
+#[test]
+fn toml() {
+    let mut s = store();
+    let node = s
+        .parse_toml("name = \"test\"\nupdated = 1979-05-27T07:32:00Z\n\n[limits]\nplayers = 50\nratio = 0.5\nlist = [1, 2]\n")
+        .unwrap();
+    assert_eq!(s.get_string(node, "name"), Ok("test".to_string()));
+    assert_eq!(
+        s.get_string(node, "updated"),
+        Ok("1979-05-27T07:32:00Z".to_string())
+    );
+    assert_eq!(s.get_path_int(node, "limits.players"), Ok(50));
+    assert_eq!(s.get_path_int(node, "limits.list[1]"), Ok(2));
+    assert_eq!(
+        s.stringify_toml(node).unwrap(),
+        "name = \"test\"\nupdated = \"1979-05-27T07:32:00Z\"\n\n[limits]\nplayers = 50\nratio = 0.5\nlist = [1, 2]\n"
+    );
+
+    let e = s.parse_toml("a = 1\nb = \n").unwrap_err();
+    assert_eq!(e.code, YamlError::Parse);
+    assert_eq!(e.line, 2);
+
+    let null = doc(&mut s, "a: ~\n");
+    assert_eq!(s.stringify_toml(null), Err(YamlError::Encode));
+}
+
+#[test]
+fn save_file() {
+    let dir = std::env::temp_dir().join("pawn-yaml-save-file");
+    fs::create_dir_all(&dir).unwrap();
+    let mut s = Store::new(&dir);
+
+    let v = val(&mut s, "value");
+    let node = obj(&mut s, vec![("key", v)]);
+    assert_eq!(s.save_file(node, "yaml_save_file.yaml"), Ok(()));
+    assert_eq!(
+        fs::read_to_string(dir.join("yaml_save_file.yaml")).unwrap(),
+        "---\nkey: value\n"
+    );
+    assert_eq!(
+        s.save_file(node, "../yaml_save_file.yaml"),
+        Err(YamlError::File)
+    );
+    assert_eq!(
+        s.save_file(node, "/tmp/yaml_save_file.yaml"),
+        Err(YamlError::File)
+    );
+}
+
+#[test]
+fn parse_file() {
+    let dir = std::env::temp_dir().join("pawn-yaml-parse-file");
+    fs::create_dir_all(&dir).unwrap();
+    let mut s = Store::new(&dir);
+
+    let v = val(&mut s, 5);
+    let node = obj(&mut s, vec![("key", v)]);
+    assert_eq!(s.save_file(node, "yaml_parse_file.yaml"), Ok(()));
+
+    let parsed = s.parse_file("yaml_parse_file.yaml").unwrap();
+    assert_eq!(s.get_int(parsed, "key"), Ok(5));
+
+    let e = s.parse_file("does_not_exist.yaml").unwrap_err();
+    assert_eq!(e.code, YamlError::File);
+    assert_eq!(e.line, -1);
+}
+
+#[test]
+fn node_type() {
+    let mut s = store();
+    let number = val(&mut s, 3);
+    assert_eq!(s.node_type(number), YamlNode::Number);
+    let boolean = val(&mut s, true);
+    assert_eq!(s.node_type(boolean), YamlNode::Boolean);
+    let string = val(&mut s, "hi");
+    assert_eq!(s.node_type(string), YamlNode::String);
+    let object = obj(&mut s, vec![]);
+    assert_eq!(s.node_type(object), YamlNode::Object);
+    let array = ints(&mut s, &[1, 2]);
+    assert_eq!(s.node_type(array), YamlNode::Array);
+    assert_eq!(s.node_type(-1), YamlNode::Null);
+}
+
+#[test]
+fn object() {
+    let mut s = store();
+    let node = obj(&mut s, vec![]);
+    assert_eq!(s.stringify(node).unwrap(), "---\n{}\n");
+
+    let a = val(&mut s, 1);
+    let b = val(&mut s, 1.5);
+    let c = val(&mut s, true);
+    let d = val(&mut s, "value");
+    let node = obj(&mut s, vec![("a", a), ("b", b), ("c", c), ("d", d)]);
+    assert_eq!(
+        s.stringify(node).unwrap(),
+        "---\na: 1\nb: 1.5\nc: true\nd: value\n"
+    );
+
+    // The values were consumed by the object.
+    assert_eq!(
+        s.object(vec![("a".to_string(), a)]),
+        Err(YamlError::InvalidNode)
+    );
+}
+
+#[test]
+fn array() {
+    let mut s = store();
+    let node = ints(&mut s, &[1, 2, 3]);
+    assert_eq!(s.stringify(node).unwrap(), "---\n- 1\n- 2\n- 3\n");
+
+    let one = val(&mut s, "value one");
+    let one = obj(&mut s, vec![("one", one)]);
+    let two = val(&mut s, "value two");
+    let two = obj(&mut s, vec![("two", two)]);
+    let node = s.array(vec![one, two]).unwrap();
+    assert_eq!(
+        s.stringify(node).unwrap(),
+        "---\n- one: value one\n- two: value two\n"
+    );
+
+    assert_eq!(s.array(vec![999]), Err(YamlError::InvalidNode));
+}
+
+#[test]
+fn append() {
+    let mut s = store();
+    let v1 = val(&mut s, "value1");
+    let v2 = val(&mut s, "value2");
+    let a = obj(&mut s, vec![("key1", v1), ("key2", v2)]);
+    let v3 = val(&mut s, "value3");
+    let b = obj(&mut s, vec![("key3", v3)]);
+    let c = s.append(a, b).unwrap();
+    assert_eq!(
+        s.stringify(c).unwrap(),
+        "---\nkey1: value1\nkey2: value2\nkey3: value3\n"
+    );
+
+    let a = ints(&mut s, &[1, 2]);
+    let b = ints(&mut s, &[3]);
+    let c = s.append(a, b).unwrap();
+    assert_eq!(s.stringify(c).unwrap(), "---\n- 1\n- 2\n- 3\n");
+
+    let a = obj(&mut s, vec![]);
+    let b = ints(&mut s, &[1]);
+    assert_eq!(s.append(a, b), Err(YamlError::WrongType));
+}
+
+#[test]
+fn set() {
+    let mut s = store();
+    let node = obj(&mut s, vec![]);
+    let inner = val(&mut s, "value");
+    let inner = obj(&mut s, vec![("key", inner)]);
+    assert_eq!(s.set_object(node, "object", inner), Ok(()));
+    assert_eq!(s.set(node, "int", 5.into()), Ok(()));
+    assert_eq!(s.set(node, "bool", true.into()), Ok(()));
+    assert_eq!(s.set(node, "string", "value".into()), Ok(()));
+    assert_eq!(
+        s.stringify(node).unwrap(),
+        "---\nobject:\n  key: value\nint: 5\nbool: true\nstring: value\n"
+    );
+
+    let array = ints(&mut s, &[]);
+    assert_eq!(s.set(array, "key", 1.into()), Err(YamlError::WrongType));
+    let scalar = val(&mut s, 1);
+    assert_eq!(s.set_object(node, "key", scalar), Err(YamlError::WrongType));
+    assert_eq!(s.set(999, "key", 1.into()), Err(YamlError::InvalidNode));
+}
+
+#[test]
+fn set_path() {
+    let mut s = store();
+    let node = obj(&mut s, vec![]);
+    let path = "vehicles[0].tuning.max_speed";
+    assert_eq!(s.set_path(node, path, 200.into()), Ok(()));
+    assert_eq!(s.set_path(node, "vehicles[0].locked", true.into()), Ok(()));
+    assert_eq!(
+        s.set_path(node, "vehicles[1].name", "Infernus".into()),
+        Ok(())
+    );
+    let colours = ints(&mut s, &[1, 3]);
+    assert_eq!(
+        s.set_path_node(node, "vehicles[1].colours", colours),
+        Ok(())
+    );
+
+    assert_eq!(s.get_path_int(node, path), Ok(200));
+    assert_eq!(s.get_path_bool(node, "vehicles[0].locked"), Ok(true));
+    assert_eq!(
+        s.get_path_string(node, "vehicles[1].name"),
+        Ok("Infernus".to_string())
+    );
+    assert_eq!(s.get_path_int(node, "vehicles[1].colours[1]"), Ok(3));
+}
+
+#[test]
+fn set_path_invalid() {
+    let mut s = store();
+    let list = ints(&mut s, &[1]);
+    let node = obj(&mut s, vec![("list", list)]);
+    assert_eq!(
+        s.set_path(node, "list[", 1.into()),
+        Err(YamlError::InvalidPath)
+    );
+    assert_eq!(
+        s.set_path(node, "list.key", 1.into()),
+        Err(YamlError::PathSegment(1))
+    );
+    assert_eq!(
+        s.set_path(node, "list[5]", 1.into()),
+        Err(YamlError::PathSegment(1))
+    );
+}
+
+#[test]
+fn keys() {
+    let mut s = store();
+    let number = val(&mut s, 1);
+    let string = val(&mut s, "value");
+    let array = ints(&mut s, &[]);
+    let node = obj(
+        &mut s,
+        vec![("number", number), ("string", string), ("array", array)],
+    );
+
+    assert!(s.has_key(node, "number"));
+    assert!(!s.has_key(node, "missing"));
+    assert_eq!(s.key_type(node, "number"), YamlNode::Number);
+    assert_eq!(s.key_type(node, "string"), YamlNode::String);
+    assert_eq!(s.key_type(node, "array"), YamlNode::Array);
+    assert_eq!(s.key_type(node, "missing"), YamlNode::Null);
+
+    assert_eq!(s.remove_key(node, "number"), Ok(()));
+    assert!(!s.has_key(node, "number"));
+    assert_eq!(s.remove_key(node, "number"), Err(YamlError::MissingKey));
+
+    let array = ints(&mut s, &[]);
+    assert!(!s.has_key(array, "key"));
+    assert_eq!(s.remove_key(array, "key"), Err(YamlError::WrongType));
+}
+
+#[test]
+fn get() {
+    let mut s = store();
+    let int = val(&mut s, 1);
+    let float = val(&mut s, 2.5);
+    let boolean = val(&mut s, true);
+    let string = val(&mut s, "value");
+    let array = ints(&mut s, &[1, 2]);
+    let node = obj(
+        &mut s,
+        vec![
+            ("int", int),
+            ("float", float),
+            ("bool", boolean),
+            ("string", string),
+            ("array", array),
+        ],
+    );
+
+    assert_eq!(s.get_int(node, "int"), Ok(1));
+    assert_eq!(s.get_float(node, "float"), Ok(2.5));
+    assert_eq!(s.get_bool(node, "bool"), Ok(true));
+    assert_eq!(s.get_string(node, "string"), Ok("value".to_string()));
+
+    let array = s.get_array(node, "array").unwrap();
+    assert_eq!(s.array_length(array), Ok(2));
+    let object = s.get_object(node, "int").unwrap();
+    assert_eq!(s.get_node_int(object), Ok(1));
+
+    assert_eq!(s.get_int(node, "missing"), Err(YamlError::MissingKey));
+    assert_eq!(s.get_float(node, "missing"), Err(YamlError::MissingKey));
+    assert_eq!(s.get_bool(node, "missing"), Err(YamlError::MissingKey));
+    assert_eq!(s.get_string(node, "missing"), Err(YamlError::MissingKey));
+    assert_eq!(s.get_array(node, "missing"), Err(YamlError::MissingKey));
+    assert_eq!(s.get_int(node, "string"), Err(YamlError::WrongType));
+    assert_eq!(s.get_array(node, "int"), Err(YamlError::WrongType));
+    assert_eq!(s.get_int(999, "int"), Err(YamlError::InvalidNode));
+}
+
+#[test]
+fn array_elements() {
+    let mut s = store();
+    let one = val(&mut s, 1);
+    let half = val(&mut s, 1.5);
+    let yes = val(&mut s, true);
+    let three = val(&mut s, "three");
+    let node = s.array(vec![one, half, yes, three]).unwrap();
+
+    assert_eq!(s.array_length(node), Ok(4));
+    assert_eq!(s.array_int(node, 0), Ok(1));
+    assert_eq!(s.array_float(node, 1), Ok(1.5));
+    assert_eq!(s.array_bool(node, 2), Ok(true));
+    assert_eq!(s.array_string(node, 3), Ok("three".to_string()));
+    let element = s.array_object(node, 3).unwrap();
+    assert_eq!(s.get_node_string(element), Ok("three".to_string()));
+
+    assert_eq!(s.array_int(node, 3), Err(YamlError::WrongType));
+    assert_eq!(s.array_int(node, 4), Err(YamlError::OutOfRange));
+    assert_eq!(s.array_int(node, -1), Err(YamlError::OutOfRange));
+}
+
+#[test]
+fn array_to_ints() {
+    let mut s = store();
+    let node = ints(&mut s, &[1, 2, 3]);
+
+    let mut got = Vec::new();
+    assert_eq!(s.array_to_ints(node, 3, &mut got), Ok(()));
+    assert_eq!(got, vec![1, 2, 3]);
+
+    let mut got = Vec::new();
+    assert_eq!(
+        s.array_to_ints(node, 2, &mut got),
+        Err(YamlError::OutOfRange)
+    );
+    assert_eq!(got, vec![1, 2]);
+}
+
+#[test]
+fn array_to_floats() {
+    let mut s = store();
+    let a = val(&mut s, 1.5);
+    let b = val(&mut s, 2.5);
+    let c = val(&mut s, "x");
+    let node = s.array(vec![a, b, c]).unwrap();
+
+    let mut got = Vec::new();
+    assert_eq!(
+        s.array_to_floats(node, 3, &mut got),
+        Err(YamlError::WrongType)
+    );
+    assert_eq!(got, vec![1.5, 2.5]);
+}
+
+#[test]
+fn array_push() {
+    let mut s = store();
+    let node = ints(&mut s, &[]);
+    let one = val(&mut s, 1);
+    assert_eq!(s.array_push(node, one), Ok(()));
+    assert_eq!(s.array_push_value(node, 2.into()), Ok(()));
+    assert_eq!(s.array_push_value(node, "three".into()), Ok(()));
+    assert_eq!(s.array_length(node), Ok(3));
+    assert_eq!(s.array_string(node, 2), Ok("three".to_string()));
+
+    let object = obj(&mut s, vec![]);
+    assert_eq!(
+        s.array_push_value(object, 1.into()),
+        Err(YamlError::WrongType)
+    );
+
+    // A failed push leaves the value to be used again.
+    let four = val(&mut s, 4);
+    assert_eq!(s.array_push(object, four), Err(YamlError::WrongType));
+    assert_eq!(s.array_push(node, four), Ok(()));
+}
+
+#[test]
+fn array_insert() {
+    let mut s = store();
+    let node = ints(&mut s, &[1, 3]);
+    let two = val(&mut s, 2);
+    assert_eq!(s.array_insert(node, 1, two), Ok(()));
+    let four = val(&mut s, 4);
+    assert_eq!(s.array_insert(node, 3, four), Ok(()));
+    let five = val(&mut s, 5);
+    assert_eq!(s.array_insert(node, 9, five), Err(YamlError::OutOfRange));
+    assert_eq!(s.get_node_int(five), Ok(5));
+
+    let mut got = Vec::new();
+    assert_eq!(s.array_to_ints(node, 4, &mut got), Ok(()));
+    assert_eq!(got, vec![1, 2, 3, 4]);
+}
+
+#[test]
+fn array_remove() {
+    let mut s = store();
+    let node = ints(&mut s, &[1, 2, 3]);
+    assert_eq!(s.array_remove(node, 0), Ok(()));
+    assert_eq!(s.array_remove(node, 2), Err(YamlError::OutOfRange));
+    assert_eq!(s.array_length(node), Ok(2));
+    assert_eq!(s.array_int(node, 0), Ok(2));
+}
+
+#[test]
+fn array_set() {
+    let mut s = store();
+    let node = ints(&mut s, &[1, 2]);
+    let two = val(&mut s, "two");
+    assert_eq!(s.array_set(node, 1, two), Ok(()));
+    let three = val(&mut s, 3);
+    assert_eq!(s.array_set(node, 2, three), Err(YamlError::OutOfRange));
+    assert_eq!(s.array_set(node, -1, three), Err(YamlError::OutOfRange));
+    assert_eq!(s.get_node_int(three), Ok(3));
+    assert_eq!(s.array_string(node, 1), Ok("two".to_string()));
+}
+
+#[test]
+fn object_iteration() {
+    let mut s = store();
+    let m4 = val(&mut s, 10);
+    let ak47 = val(&mut s, 12);
+    let node = obj(&mut s, vec![("m4", m4), ("ak47", ak47)]);
+
+    assert_eq!(s.object_length(node), Ok(2));
+    assert_eq!(s.object_key(node, 0), Ok("m4".to_string()));
+    assert_eq!(s.object_key(node, 1), Ok("ak47".to_string()));
+    assert_eq!(s.object_key(node, 2), Err(YamlError::OutOfRange));
+
+    let value = s.object_value(node, 1).unwrap();
+    assert_eq!(s.get_node_int(value), Ok(12));
+    assert_eq!(s.object_value(node, 2), Err(YamlError::OutOfRange));
+
+    let array = ints(&mut s, &[]);
+    assert_eq!(s.object_length(array), Err(YamlError::WrongType));
+}
+
+#[test]
+fn get_path() {
+    let mut s = store();
+    let node = doc(
+        &mut s,
+        "server:\n  name: test\n  gravity: 0.5\n  limits:\n    players: [50, 100]\n  open: true\n",
+    );
+
+    assert_eq!(s.get_path_int(node, "server.limits.players[1]"), Ok(100));
+    assert_eq!(s.get_path_float(node, "server.gravity"), Ok(0.5));
+    assert_eq!(s.get_path_bool(node, "server.open"), Ok(true));
+    assert_eq!(
+        s.get_path_string(node, "server.name"),
+        Ok("test".to_string())
+    );
+    let players = s.get_path_node(node, "server.limits.players").unwrap();
+    assert_eq!(s.node_type(players), YamlNode::Array);
+}
+
+#[test]
+fn get_path_invalid() {
+    let mut s = store();
+    let node = doc(&mut s, "server:\n  limits:\n    players: [50]\n");
+
+    assert_eq!(
+        s.get_path_int(node, "server..limits"),
+        Err(YamlError::InvalidPath)
+    );
+    assert_eq!(
+        s.get_path_int(node, "server.limits"),
+        Err(YamlError::WrongType)
+    );
+    assert_eq!(
+        s.get_path_int(node, "server.missing.players[0]"),
+        Err(YamlError::PathSegment(1))
+    );
+    assert_eq!(
+        s.get_path_int(node, "server.limits.players[1]"),
+        Err(YamlError::PathSegment(3))
+    );
+    assert_eq!(YamlError::PathSegment(3).code(), 103);
+}
+
+#[test]
+fn get_node() {
+    let mut s = store();
+    let int = val(&mut s, 1);
+    assert_eq!(s.get_node_int(int), Ok(1));
+    let float = val(&mut s, 1.25);
+    assert_eq!(s.get_node_float(float), Ok(1.25));
+    let boolean = val(&mut s, true);
+    assert_eq!(s.get_node_bool(boolean), Ok(true));
+    let string = val(&mut s, "value");
+    assert_eq!(s.get_node_string(string), Ok("value".to_string()));
+
+    // Reading a garbage collected node consumes it.
+    assert_eq!(s.get_node_int(int), Err(YamlError::InvalidNode));
+    let string = val(&mut s, "value");
+    assert_eq!(s.get_node_int(string), Err(YamlError::WrongType));
+}
+
+#[test]
+fn cleanup() {
+    let mut s = store();
+    let node = obj(&mut s, vec![]);
+    assert_eq!(s.cleanup(node, true), Ok(()));
+    assert_eq!(s.cleanup(node, true), Err(YamlError::InvalidNode));
+}
+
+#[test]
+fn toggle_gc() {
+    let mut s = store();
+    let v = val(&mut s, "value");
+    let node = obj(&mut s, vec![("key", v)]);
+    assert_eq!(s.toggle_gc(node, false), Ok(()));
+
+    // Non-collected nodes survive being consumed and automatic cleanup.
+    let parent = ints(&mut s, &[]);
+    assert_eq!(s.array_push(parent, node), Ok(()));
+    assert_eq!(s.cleanup(node, true), Err(YamlError::InvalidNode));
+    assert_eq!(s.get_string(node, "key"), Ok("value".to_string()));
+
+    assert_eq!(s.cleanup(node, false), Ok(()));
+    assert_eq!(s.cleanup(node, false), Err(YamlError::InvalidNode));
+    assert_eq!(s.toggle_gc(node, true), Err(YamlError::InvalidNode));
+}
+
+#[test]
+fn stale_handle() {
+    let mut s = store();
+    let a = val(&mut s, 1);
+    assert_eq!(s.get_node_int(a), Ok(1));
+
+    // The freed slot is reused, but the old handle must not reach the new node.
+    let b = val(&mut s, 2);
+    assert_ne!(a, b);
+    assert!(a > 0 && b > 0);
+    assert_eq!(s.get_node_int(a), Err(YamlError::InvalidNode));
+    assert_eq!(s.cleanup(a, false), Err(YamlError::InvalidNode));
+    assert_eq!(s.node_type(a), YamlNode::Null);
+    assert_eq!(s.get_node_int(b), Ok(2));
+
+    assert_eq!(s.get_node_int(0), Err(YamlError::InvalidNode));
+    assert_eq!(s.get_node_int(-1), Err(YamlError::InvalidNode));
+}
+
+#[test]
+fn stale_handle_generation_wrap() {
+    let mut s = store();
+    let a = val(&mut s, 1);
+    assert_eq!(s.get_node_int(a), Ok(1));
+
+    // Freed slots are only reused once many others are free, so `a` stays
+    // invalid well beyond the number of generations a handle can hold.
+    for i in 0..crate::pool::GENERATION_MAX as i32 + 1 {
+        let b = val(&mut s, i);
+        assert_ne!(a, b);
+        assert_eq!(s.get_node_int(a), Err(YamlError::InvalidNode));
+        assert_eq!(s.get_node_int(b), Ok(i));
+    }
+    assert_eq!(s.get_node_int(a), Err(YamlError::InvalidNode));
+
+    // Once a slot runs out of generations it wraps around and keeps being
+    // used rather than the pool running out of slots.
+    s.nodes = GarbageCollectedPool::with_capacity(1);
+    for i in 0..2 * crate::pool::GENERATION_MAX as i32 + 2 {
+        let b = val(&mut s, i);
+        assert!(b > 0);
+        assert_eq!(s.get_node_int(b), Ok(i));
+    }
+}
+
+#[test]
+fn pool_full() {
+    let mut s = store();
+    s.nodes = GarbageCollectedPool::with_capacity(3);
+    let root = doc(&mut s, "a: {b: 1}\nlist: [1]\n");
+    let a = s.get_object(root, "a").unwrap();
+    let list = s.get_object_ref(root, "list").unwrap();
+
+    assert_eq!(s.get_object(root, "a"), Err(YamlError::PoolFull));
+    assert_eq!(s.get_object_ref(a, "b"), Err(YamlError::PoolFull));
+    assert_eq!(s.array_object(list, 0), Err(YamlError::PoolFull));
+    assert_eq!(s.alloc(1.into()), Err(YamlError::PoolFull));
+    let e = s.parse("a: 1", false).unwrap_err();
+    assert_eq!(e.code, YamlError::PoolFull);
+    assert_eq!(
+        s.parse_round_trip("a: 1").unwrap_err().code,
+        YamlError::PoolFull
+    );
+    assert_eq!(
+        s.parse_all("a: 1", 1).unwrap_err().code,
+        YamlError::PoolFull
+    );
+
+    // Freeing a node makes room for another.
+    assert_eq!(s.cleanup(a, false), Ok(()));
+    assert!(s.get_object(root, "a").is_ok());
+}
+
+#[test]
+fn free_owned() {
+    let mut s = store();
+    s.set_owner(1);
+    let a = val(&mut s, 1);
+    let b = val(&mut s, 2);
+    assert_eq!(s.toggle_gc(b, false), Ok(()));
+    s.set_owner(2);
+    let c = val(&mut s, 3);
+
+    assert_eq!(s.free_owned(1), 2);
+    assert_eq!(s.get_node_int(a), Err(YamlError::InvalidNode));
+    assert_eq!(s.get_node_int(b), Err(YamlError::InvalidNode));
+    assert_eq!(s.get_node_int(c), Ok(3));
+    assert_eq!(s.free_owned(1), 0);
+}
+
+#[test]
+fn stats() {
+    let mut s = store();
+    assert_eq!(s.stats(), Stats::default());
+
+    let a = val(&mut s, "value");
+    let b = val(&mut s, 1);
+    assert_eq!(s.toggle_gc(b, false), Ok(()));
+    let stats = s.stats();
+    assert_eq!(stats.live, 2);
+    assert_eq!(stats.gc_disabled, 1);
+    assert!(stats.bytes >= "value".len());
+
+    assert_eq!(s.cleanup(a, true), Ok(()));
+    assert_eq!(s.stats().live, 1);
+}
+
+#[test]
+fn dump_live_nodes() {
+    let dir = std::env::temp_dir().join("pawn-yaml-dump-live-nodes");
+    fs::create_dir_all(&dir).unwrap();
+    let mut s = Store::new(&dir);
+
+    s.set_native("YAML_Int");
+    let a = val(&mut s, 1);
+    assert_eq!(s.toggle_gc(a, false), Ok(()));
+    s.set_native("YAML_String");
+    let b = val(&mut s, "x".repeat(100));
+
+    assert_eq!(s.dump_live_nodes("live.txt"), Ok(()));
+    let dump = fs::read_to_string(dir.join("live.txt")).unwrap();
+    let lines: Vec<&str> = dump.lines().collect();
+    assert_eq!(lines[0], format!("{}\tNumber\tgc=false\tYAML_Int\t1", a));
+    assert_eq!(
+        lines[1],
+        format!("{}\tString\tgc=true\tYAML_String\t{}...", b, "x".repeat(64))
+    );
+    assert_eq!(s.dump_live_nodes("../live.txt"), Err(YamlError::File));
+}
+
+#[test]
+fn shared_children() {
+    let mut s = store();
+    let root = doc(
+        &mut s,
+        "server:\n  limits:\n    players: 50\n  list: [1, 2]\n",
+    );
+    let server = s.get_object(root, "server").unwrap();
+    let limits = s.get_path_node(root, "server.limits").unwrap();
+    let nested = s.get_object(server, "limits").unwrap();
+    let element = s.get_path_node(root, "server.list[1]").unwrap();
+
+    // Writes through a child are not seen by the parent or other children.
+    assert_eq!(s.set(limits, "players", 100.into()), Ok(()));
+    assert_eq!(s.get_int(limits, "players"), Ok(100));
+    assert_eq!(s.get_int(nested, "players"), Ok(50));
+    assert_eq!(s.get_path_int(root, "server.limits.players"), Ok(50));
+
+    // Writes through the parent are not seen by its children.
+    assert_eq!(
+        s.set_path(root, "server.limits.players", 200.into()),
+        Ok(())
+    );
+    assert_eq!(s.get_int(nested, "players"), Ok(50));
+    assert_eq!(s.get_path_int(server, "limits.players"), Ok(50));
+    assert_eq!(s.get_path_int(root, "server.limits.players"), Ok(200));
+
+    // Children outlive their parent and can be consumed into other nodes.
+    assert_eq!(s.cleanup(root, false), Ok(()));
+    assert_eq!(s.get_node_int(element), Ok(2));
+    let parent = ints(&mut s, &[]);
+    assert_eq!(s.array_push(parent, nested), Ok(()));
+    assert_eq!(s.stringify(parent).unwrap(), "---\n- players: 50\n");
+}
+
+fn document_id(s: &Store, node: i32) -> usize {
+    match s.nodes.peek(node) {
+        Some(Entry::Node(n)) => n.document_id(),
+        _ => panic!("not a node: {}", node),
+    }
+}
+
+#[test]
+fn shared_children_copies() {
+    let mut s = store();
+    let cfg = doc(&mut s, "section:\n  a: 1\nother: [1, 2, 3]\n");
+    let id = document_id(&s, cfg);
+
+    // Each write to the parent only copies the subtrees of the children still
+    // sharing it, never the whole document.
+    let mut sections = Vec::new();
+    for i in 0..3 {
+        let section = s.get_object(cfg, "section").unwrap();
+        assert_eq!(document_id(&s, section), id);
+        assert_eq!(s.set(cfg, &format!("k{}", i), i.into()), Ok(()));
+        assert_eq!(document_id(&s, cfg), id);
+        assert_ne!(document_id(&s, section), id);
+        assert_eq!(s.stringify(section).unwrap(), "---\na: 1\n");
+        sections.push(section);
+    }
+
+    // Detached children no longer hold the parent's document.
+    let detached: Vec<usize> = sections.iter().map(|n| document_id(&s, *n)).collect();
+    assert_eq!(s.set(cfg, "k0", 10.into()), Ok(()));
+    assert_eq!(document_id(&s, cfg), id);
+    for (n, id) in sections.iter().zip(detached) {
+        assert_eq!(document_id(&s, *n), id);
+    }
+    assert_eq!(s.get_int(cfg, "k0"), Ok(10));
+    assert_eq!(s.get_int(cfg, "k2"), Ok(2));
+}
+
+#[test]
+fn references() {
+    let mut s = store();
+    let root = doc(
+        &mut s,
+        "server:\n  limits:\n    players: 50\n  list: [1, 2]\n",
+    );
+    let server = s.get_object_ref(root, "server").unwrap();
+    let limits = s.get_object_ref(server, "limits").unwrap();
+    let list = s.get_object_ref(server, "list").unwrap();
+    let element = s.array_object_ref(list, 1).unwrap();
+
+    // Writes through a reference are seen by the root and vice versa.
+    assert_eq!(s.set(limits, "players", 100.into()), Ok(()));
+    assert_eq!(s.array_push_value(list, 3.into()), Ok(()));
+    assert_eq!(
+        s.stringify(root).unwrap(),
+        "---\nserver:\n  limits:\n    players: 100\n  list:\n    - 1\n    - 2\n    - 3\n"
+    );
+    assert_eq!(s.set_path(root, "server.list[1]", 20.into()), Ok(()));
+    assert_eq!(s.array_int(list, 1), Ok(20));
+    assert_eq!(s.get_node_int(element), Ok(20));
+
+    assert_eq!(
+        s.get_object_ref(root, "missing"),
+        Err(YamlError::MissingKey)
+    );
+    assert_eq!(s.array_object_ref(list, 5), Err(YamlError::OutOfRange));
+
+    // References to array items fail once the array is resized rather than
+    // pointing at whichever item moved into their place.
+    let names = doc(&mut s, "names: [a, b, c]\nscores: [1]\n");
+    let letters = s.get_object_ref(names, "names").unwrap();
+    let b = s.array_object_ref(letters, 1).unwrap();
+    let scores = s.get_object_ref(names, "scores").unwrap();
+    let score = s.array_object_ref(scores, 0).unwrap();
+    assert_eq!(s.array_remove(letters, 0), Ok(()));
+    assert_eq!(s.node_type(b), YamlNode::Null);
+    assert_eq!(s.get_node_string(b), Err(YamlError::InvalidNode));
+    assert_eq!(s.get_node_int(score), Ok(1));
+    let c = s.array_object_ref(letters, 1).unwrap();
+    let z = val(&mut s, "z");
+    assert_eq!(s.array_insert(letters, 0, z), Ok(()));
+    assert_eq!(s.get_node_string(c), Err(YamlError::InvalidNode));
+    assert_eq!(s.get_path_string(names, "names[2]"), Ok("c".to_string()));
+
+    // References fail once the value or the parent they point to is gone.
+    assert_eq!(s.remove_key(server, "limits"), Ok(()));
+    assert_eq!(s.get_int(limits, "players"), Err(YamlError::InvalidNode));
+    assert_eq!(s.cleanup(root, false), Ok(()));
+    assert_eq!(s.array_length(list), Err(YamlError::InvalidNode));
+}
+
+const CONFIG: &str = "# server settings
+server:
+  name: 'Southclaws'' Server'   # shown in the browser
+  port: 7777
+
+  # gameplay
+  limits: &limits
+    players: 50
+    vehicles: 2000
+  motd: \"welcome\"
+  admins:
+    - alice
+    - bob
+  empty:
+copy: *limits
+";
+
+#[test]
+fn round_trip() {
+    let mut s = store();
+    let root = s.parse_round_trip(CONFIG).unwrap();
+    assert_eq!(s.stringify(root).unwrap(), CONFIG);
+
+    // Scalars are replaced in place in their original style.
+    assert_eq!(s.set_path(root, "server.port", 7778.into()), Ok(()));
+    assert_eq!(
+        s.set_path(root, "server.name", "Bob's Server".into()),
+        Ok(())
+    );
+    assert_eq!(s.set_path(root, "server.motd", "say \"hi\"".into()), Ok(()));
+    assert_eq!(s.set_path(root, "server.empty", true.into()), Ok(()));
+    assert_eq!(
+        s.stringify(root).unwrap(),
+        CONFIG
+            .replace("7777", "7778")
+            .replace("'Southclaws'' Server'", "'Bob''s Server'")
+            .replace("\"welcome\"", "\"say \\\"hi\\\"\"")
+            .replace("empty:", "empty: true")
+    );
+}
+
+#[test]
+fn round_trip_structure() {
+    let mut s = store();
+    let root = s.parse_round_trip(CONFIG).unwrap();
+    let server = s.get_object_ref(root, "server").unwrap();
+    let admins = s.get_object_ref(server, "admins").unwrap();
+
+    // Removed entries take their lines with them and new entries are added
+    // after the last one at the same indentation.
+    assert_eq!(s.remove_key(server, "port"), Ok(()));
+    assert_eq!(s.set(server, "gamemode", "survival".into()), Ok(()));
+    assert_eq!(s.array_remove(admins, 0), Ok(()));
+    assert_eq!(s.array_push_value(admins, "carol".into()), Ok(()));
+    assert_eq!(
+        s.stringify(root).unwrap(),
+        CONFIG
+            .replace("  port: 7777\n", "")
+            .replace("    - alice\n", "")
+            .replace("    - bob\n", "    - bob\n    - carol\n")
+            .replace("  empty:\n", "  empty:\n  gamemode: survival\n")
+    );
+
+    // Changing a value which is aliased elsewhere can't be done in place, so
+    // the document is encoded in full.
+    assert_eq!(s.set_path(root, "server.limits.players", 60.into()), Ok(()));
+    let out = s.stringify(root).unwrap();
+    assert!(out.starts_with("---\n"));
+    let copy = doc(&mut s, &out);
+    assert_eq!(s.get_path_int(copy, "copy.players"), Ok(50));
+}
+
+#[test]
+fn round_trip_children() {
+    let mut s = store();
+    let root = s.parse_round_trip("a: 1 # one\nb: [1, 2]\n").unwrap();
+
+    // Flow collections are replaced as a whole.
+    assert_eq!(s.set_path(root, "b[0]", 3.into()), Ok(()));
+    assert_eq!(s.stringify(root).unwrap(), "a: 1 # one\nb:\n  - 3\n  - 2\n");
+
+    // Children and copies are encoded normally.
+    let b = s.get_object(root, "b").unwrap();
+    assert_eq!(s.stringify(b).unwrap(), "---\n- 3\n- 2\n");
+    let copy = doc(&mut s, "a: 1 # one\n");
+    assert_eq!(s.stringify(copy).unwrap(), "---\na: 1\n");
+}
+
+#[test]
+fn round_trip_fallback() {
+    let mut s = store();
+
+    // Only the last of a repeated key is decoded, so its spans don't line up
+    // with the entries and the document is encoded in full.
+    let root = s.parse_round_trip("a: 1\na: 2\nb: 3\n").unwrap();
+    assert_eq!(s.set(root, "b", 4.into()), Ok(()));
+    let out = s.stringify(root).unwrap();
+    let copy = doc(&mut s, &out);
+    assert_eq!(s.get_int(copy, "a"), Ok(2));
+    assert_eq!(s.get_int(copy, "b"), Ok(4));
+
+    // Inserting after a `|+` scalar would take its trailing blank line.
+    let root = s.parse_round_trip("a: |+\n  text\n\n").unwrap();
+    assert_eq!(s.set(root, "b", 1.into()), Ok(()));
+    let out = s.stringify(root).unwrap();
+    let copy = doc(&mut s, &out);
+    assert_eq!(s.get_string(copy, "a"), Ok("text\n\n".to_string()));
+    assert_eq!(s.get_int(copy, "b"), Ok(1));
+}
+
+#[test]
+fn round_trip_stringify_all() {
+    let mut s = store();
+    let a = s.parse_round_trip("a: 1 # one").unwrap();
+    let b = s.parse_round_trip("b: 2 # two\n").unwrap();
+    let out = s.stringify_all(&[a, b]).unwrap();
+    assert_eq!(out, "---\na: 1 # one\n---\nb: 2 # two\n");
+    let (nodes, total) = s.parse_all(&out, 2).unwrap();
+    assert_eq!(total, 2);
+    assert_eq!(s.get_int(nodes[1], "b"), Ok(2));
+}
+
+#[test]
+fn validate() {
+    let mut s = store();
+    let schema = doc(
+        &mut s,
+        "type: object
+required: [name, port, admins]
+additionalProperties: false
+properties:
+  name: {type: string, maxLength: 8}
+  port: {type: integer, minimum: 1, maximum: 65535}
+  mode: {enum: [survival, freeroam]}
+  admins: {type: array, minItems: 1, items: {type: string}}
+  gravity: {type: [number, 'null']}
+",
+    );
+
+    let valid = doc(
+        &mut s,
+        "name: test\nport: 7777\nmode: survival\nadmins: [alice]\ngravity: 0.008\n",
+    );
+    assert_eq!(s.validate(valid, schema), Ok(vec![]));
+
+    let invalid = doc(
+        &mut s,
+        "name: a long server name\nport: 0\nmode: deathmatch\nadmins: [alice, 5]\ngravity: low\nextra: 1\n",
+    );
+    assert_eq!(
+        s.validate(invalid, schema),
+        Ok(vec![
+            "extra: unexpected key".to_string(),
+            "name: length 18 is greater than 8".to_string(),
+            "port: 0 is less than 1".to_string(),
+            "mode: deathmatch is not one of survival, freeroam".to_string(),
+            "admins[1]: expected string, got integer".to_string(),
+            "gravity: expected number or null, got string".to_string(),
+        ])
+    );
+
+    let missing = doc(&mut s, "[1, 2]");
+    assert_eq!(
+        s.validate(missing, schema),
+        Ok(vec!["(root): expected object, got array".to_string()])
+    );
+    let empty = doc(&mut s, "{}");
+    assert_eq!(
+        s.validate(empty, schema),
+        Ok(vec![
+            "name: missing required key".to_string(),
+            "port: missing required key".to_string(),
+            "admins: missing required key".to_string(),
+        ])
+    );
+
+    let typo = doc(&mut s, "properties:\n  port: {minimun: 1}\n");
+    assert_eq!(s.validate(empty, typo), Err(YamlError::Schema));
+    let bad_type = doc(&mut s, "type: float\n");
+    assert_eq!(s.validate(empty, bad_type), Err(YamlError::Schema));
+}
+
+#[test]
+fn unpack() {
+    let mut s = store();
+    let node = doc(
+        &mut s,
+        "name: Южный\nscore: 50\nstats: {health: 75.5, admin: true}\n",
+    );
+    let format = "name:s[6] score:i stats.health:f, stats.admin:b";
+
+    let mut dest = vec![-1; 9];
+    assert_eq!(s.unpack(node, format, &mut dest), Ok(()));
+    assert_eq!(&dest[..6], &[0xDE, 0xE6, 0xED, 0xFB, 0xE9, 0]);
+    assert_eq!(dest[6], 50);
+    assert_eq!(f32::from_bits(dest[7] as u32), 75.5);
+    assert_eq!(dest[8], 1);
+
+    // Strings are truncated to fit and fields that can't be read are skipped.
+    let mut dest = vec![-1; 7];
+    assert_eq!(
+        s.unpack(node, "name:s[3] missing:i score:f stats:i", &mut dest),
+        Err(YamlError::PathSegment(0))
+    );
+    assert_eq!(&dest[..4], &[0xDE, 0xE6, 0, -1]);
+    assert_eq!(f32::from_bits(dest[4] as u32), 50.0);
+    assert_eq!(dest[5], -1);
+
+    assert_eq!(
+        s.unpack(node, "score:i", &mut []),
+        Err(YamlError::OutOfRange)
+    );
+    assert_eq!(s.unpack(node, "score:x", &mut dest), Err(YamlError::Format));
+    assert_eq!(
+        s.unpack(node, "name:s[0]", &mut dest),
+        Err(YamlError::Format)
+    );
+}
+
+#[test]
+fn pack() {
+    let mut s = store();
+    let mut src = vec![0; 9];
+    pack::write_string(&mut src[..6], "Южный");
+    src[6] = 50;
+    src[7] = 75.5f32.to_bits() as i32;
+    src[8] = 1;
+
+    let node = s
+        .pack("name:s[6] score:i stats.health:f stats.admin:b", &src)
+        .unwrap();
+    assert_eq!(
+        s.stringify(node).unwrap(),
+        "---\nname: Южный\nscore: 50\nstats:\n  health: 75.5\n  admin: true\n"
+    );
+
+    assert_eq!(
+        s.pack("score:i score.x:i", &src),
+        Err(YamlError::PathSegment(1))
+    );
+    assert_eq!(s.pack("name:s[10]", &src[..4]), Err(YamlError::OutOfRange));
+}
+
+#[test]
+fn get_or() {
+    let mut s = store();
+    let node = doc(
+        &mut s,
+        "players: 50\ngravity: 0.5\nopen: true\nname: test\n",
+    );
+
+    assert_eq!(s.get_int_or(node, "players", 10), 50);
+    assert_eq!(s.get_int_or(node, "missing", 10), 10);
+    assert_eq!(s.get_int_or(node, "name", 10), 10);
+    assert_eq!(s.get_float_or(node, "gravity", 1.0), 0.5);
+    assert_eq!(s.get_float_or(node, "missing", 1.0), 1.0);
+    assert!(s.get_bool_or(node, "open", false));
+    assert!(!s.get_bool_or(node, "missing", false));
+    assert_eq!(
+        s.get_string_or(node, "name", "default"),
+        ("test".to_string(), true)
+    );
+    assert_eq!(
+        s.get_string_or(node, "players", "default"),
+        ("default".to_string(), false)
+    );
+    assert_eq!(s.get_int_or(-1, "players", 10), 10);
+}
+
+#[test]
+fn loose_types() {
+    let mut s = store();
+    let node = doc(
+        &mut s,
+        "players: \"50\"\nratio: 50.7\ngravity: \" 0.5 \"\nopen: yes\nclosed: 'off'\nflag: 1\nname: test\nport: 7777\n",
+    );
+
+    assert_eq!(s.get_int(node, "players"), Err(YamlError::WrongType));
+    assert_eq!(s.get_int_loose(node, "players"), Ok(50));
+    assert_eq!(s.get_int_loose(node, "ratio"), Ok(50));
+    assert_eq!(s.get_int_loose(node, "open"), Err(YamlError::WrongType));
+    assert_eq!(s.get_float_loose(node, "gravity"), Ok(0.5));
+    assert_eq!(s.get_float_loose(node, "name"), Err(YamlError::WrongType));
+    assert_eq!(s.get_bool_loose(node, "open"), Ok(true));
+    assert_eq!(s.get_bool_loose(node, "closed"), Ok(false));
+    assert_eq!(s.get_bool_loose(node, "flag"), Ok(true));
+    assert_eq!(s.get_bool_loose(node, "port"), Err(YamlError::WrongType));
+    assert_eq!(s.get_string_loose(node, "port"), Ok("7777".to_string()));
+
+    // With loose types enabled every getter converts.
+    s.set_loose(true);
+    assert_eq!(s.get_int(node, "players"), Ok(50));
+    assert_eq!(s.get_path_bool(node, "open"), Ok(true));
+    let flag = s.get_object(node, "flag").unwrap();
+    assert_eq!(s.get_node_string(flag), Ok("1".to_string()));
+    let mut dest = [0; 2];
+    assert_eq!(s.unpack(node, "players:i open:b", &mut dest), Ok(()));
+    assert_eq!(dest, [50, 1]);
+    let mut dest = [0; 4];
+    assert_eq!(s.unpack(node, "port:s[4]", &mut dest), Ok(()));
+    assert_eq!(dest, [55, 55, 55, 0]);
+    let floats = doc(&mut s, "[\"1.5\", 2]");
+    let mut output = Vec::new();
+    assert_eq!(s.array_to_floats(floats, 2, &mut output), Ok(()));
+    assert_eq!(output, [1.5, 2.0]);
+    s.set_loose(false);
+    assert_eq!(s.get_int(node, "players"), Err(YamlError::WrongType));
+    assert_eq!(
+        s.unpack(node, "port:s[4]", &mut dest),
+        Err(YamlError::WrongType)
+    );
+    let floats = doc(&mut s, "[\"1.5\", 2]");
+    assert_eq!(
+        s.array_to_floats(floats, 2, &mut Vec::new()),
+        Err(YamlError::WrongType)
+    );
+}
+
+#[test]
+fn int64() {
+    let mut s = store();
+    let node = doc(
+        &mut s,
+        "id: 1234567890123456789\nhuge: 18446744073709551615\nsmall: -5\nids: [1, 4294967296]\n",
+    );
+
+    assert_eq!(s.get_int(node, "id"), Err(YamlError::OutOfRange));
+    assert_eq!(s.get_int(node, "huge"), Err(YamlError::OutOfRange));
+    assert_eq!(s.get_int(node, "small"), Ok(-5));
+    let ids = s.get_object(node, "ids").unwrap();
+    let mut output = Vec::new();
+    assert_eq!(
+        s.array_to_ints(ids, 2, &mut output),
+        Err(YamlError::OutOfRange)
+    );
+
+    assert_eq!(s.get_int64(node, "id"), Ok(1234567890123456789));
+    assert_eq!(s.get_int64(node, "huge"), Err(YamlError::OutOfRange));
+    assert_eq!(s.get_int64(node, "small"), Ok(-5));
+    assert_eq!(
+        s.get_int_string(node, "huge"),
+        Ok("18446744073709551615".to_string())
+    );
+    assert_eq!(s.get_int_string(node, "small"), Ok("-5".to_string()));
+
+    assert_eq!(
+        s.set_int_string(node, "copy", "1234567890123456789"),
+        Ok(())
+    );
+    assert_eq!(s.get_int64(node, "copy"), Ok(1234567890123456789));
+    assert_eq!(
+        s.set_int_string(node, "copy", "1.5"),
+        Err(YamlError::WrongType)
+    );
+}